@@ -0,0 +1,91 @@
+use std::io::{self, Write};
+
+/// 書き込まれた各行の先頭に、grep の `-n`/`-b` と同じ形式で行番号やバイトオフセットを付けるアダプタ
+///
+/// 両方を付けるときは `行番号:オフセット:行` の順に出力する
+///
+/// オフセットは書き込まれたバイト数から数えるので、入力をそのまま書き出すモードでのみ入力上の位置と一致する。
+/// 圧縮された入力では伸長後のデータ上の位置になる
+pub(crate) struct Annotator<W: Write> {
+    out: W,
+    number: bool,
+    byte_offset: bool,
+    /// 次に出力する行の行番号
+    line: u64,
+    /// 次に出力するバイトのファイル先頭からの位置
+    offset: u64,
+    at_line_start: bool,
+}
+
+impl<W: Write> Annotator<W> {
+    /// `start` は最初に書き込まれるバイトのファイル上の位置
+    pub(crate) fn new(out: W, number: bool, byte_offset: bool, start: u64) -> Self {
+        Annotator {
+            out,
+            number,
+            byte_offset,
+            line: 1,
+            offset: start,
+            at_line_start: true,
+        }
+    }
+
+    fn write_prefix(&mut self) -> io::Result<()> {
+        if self.number {
+            write!(self.out, "{}:", self.line)?;
+        }
+        if self.byte_offset {
+            write!(self.out, "{}:", self.offset)?;
+        }
+        self.line += 1;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Annotator<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for chunk in buf.split_inclusive(|&b| b == b'\n') {
+            if self.at_line_start {
+                self.write_prefix()?;
+            }
+            self.out.write_all(chunk)?;
+            self.offset += chunk.len() as u64;
+            self.at_line_start = chunk.ends_with(b"\n");
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotate(chunks: &[&str], number: bool, byte_offset: bool, start: u64) -> String {
+        let mut out = vec![];
+        let mut annotator = Annotator::new(&mut out, number, byte_offset, start);
+        for chunk in chunks {
+            annotator.write_all(chunk.as_bytes()).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn 行の途中で分割して書き込まれても行頭にだけ付ける() {
+        assert_eq!(
+            annotate(&["on", "e\ntw", "o\n\nthr"], true, false, 0),
+            "1:one\n2:two\n3:\n4:thr"
+        );
+    }
+
+    #[test]
+    fn 開始位置からのバイトオフセットを行番号の後に付ける() {
+        assert_eq!(
+            annotate(&["ab\nあ\ncd\n"], true, true, 10),
+            "1:10:ab\n2:13:あ\n3:17:cd\n"
+        );
+    }
+}
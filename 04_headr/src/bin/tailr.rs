@@ -0,0 +1,7 @@
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args = headr::get_tail_args();
+    headr::run(args);
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,114 @@
+use std::{
+    cell::Cell,
+    io::{self, BufRead, Read, Write},
+    rc::Rc,
+};
+
+use crate::Input;
+
+/// 複数の入力を順に読み進める1つのストリーム。開けない入力は報告して読み飛ばす
+pub(crate) struct Concat {
+    paths: Vec<String>,
+    next: usize,
+    current: Option<Input>,
+    decompress: bool,
+    /// 読み出しが次の入力に移ったとき、その入力の番号を見出しのために知らせる
+    switched: Option<Rc<Cell<Option<usize>>>>,
+}
+
+impl Concat {
+    pub(crate) fn new(
+        paths: Vec<String>,
+        decompress: bool,
+        switched: Option<Rc<Cell<Option<usize>>>>,
+    ) -> Self {
+        Concat {
+            paths,
+            next: 0,
+            current: None,
+            decompress,
+            switched,
+        }
+    }
+}
+
+impl Read for Concat {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for Concat {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        loop {
+            let Some(current) = &mut self.current else {
+                let Some(path) = self.paths.get(self.next) else {
+                    return Ok(&[]);
+                };
+                self.next += 1;
+                match crate::open(path, self.decompress) {
+                    Ok(input) => self.current = Some(input),
+                    Err(err) => eprintln!("{}: {}", path, err),
+                }
+                continue;
+            };
+            if current.fill_buf()?.is_empty() {
+                self.current = None;
+                continue;
+            }
+            break;
+        }
+        if let Some(switched) = &self.switched {
+            switched.set(Some(self.next - 1));
+        }
+        match &mut self.current {
+            Some(current) => current.fill_buf(),
+            None => Ok(&[]),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Some(current) = &mut self.current {
+            current.consume(amt);
+        }
+    }
+}
+
+/// [`Concat`] が次の入力に移ったあと、最初の書き込みの前にその入力の見出しを出力する
+pub(crate) struct HeaderWriter<'a, W: Write> {
+    out: W,
+    paths: &'a [String],
+    switched: Rc<Cell<Option<usize>>>,
+    last: Option<usize>,
+}
+
+impl<'a, W: Write> HeaderWriter<'a, W> {
+    pub(crate) fn new(out: W, paths: &'a [String], switched: Rc<Cell<Option<usize>>>) -> Self {
+        HeaderWriter {
+            out,
+            paths,
+            switched,
+            last: None,
+        }
+    }
+}
+
+impl<W: Write> Write for HeaderWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(index) = self.switched.take() {
+            if self.last != Some(index) {
+                crate::print_header(&mut self.out, &self.paths[index], self.last.is_none())?;
+                self.last = Some(index);
+            }
+        }
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -0,0 +1,121 @@
+use std::io::{self, BufRead, BufReader};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+/// bzip2 のブロックの先頭に置かれるマジックナンバー (円周率)
+const BZIP2_BLOCK_MAGIC: [u8; 6] = [0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+/// bzip2 のストリームの終端に置かれるマジックナンバー (円周率の平方根)
+const BZIP2_END_MAGIC: [u8; 6] = [0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+
+/// 先頭のマジックバイトから圧縮形式を判定する。入力は読み進めない
+pub(crate) fn detect(reader: &mut impl BufRead) -> io::Result<Option<Compression>> {
+    let magic = reader.fill_buf()?;
+    // gzip は圧縮方式 (deflate) まで確かめる
+    let compression = if magic.starts_with(&[0x1f, 0x8b, 0x08]) {
+        Some(Compression::Gzip)
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some(Compression::Zstd)
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Some(Compression::Xz)
+    } else if is_bzip2(magic) {
+        Some(Compression::Bzip2)
+    } else {
+        None
+    };
+    Ok(compression)
+}
+
+/// "BZh" で始まるテキストと区別するため、ブロックサイズの数字と
+/// 最初のブロックまたはストリーム終端のマジックナンバーまで確かめる
+fn is_bzip2(magic: &[u8]) -> bool {
+    match magic {
+        [b'B', b'Z', b'h', b'1'..=b'9', rest @ ..] if rest.len() >= 6 => {
+            rest[..6] == BZIP2_BLOCK_MAGIC || rest[..6] == BZIP2_END_MAGIC
+        }
+        _ => false,
+    }
+}
+
+/// 読み出した分だけ伸長するリーダーを返す。先頭だけを出力するときは残りを伸長しない
+pub(crate) fn decoder<'a>(
+    compression: Compression,
+    reader: impl BufRead + 'a,
+) -> io::Result<Box<dyn BufRead + 'a>> {
+    Ok(match compression {
+        Compression::Gzip => Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Compression::Xz => Box::new(BufReader::new(xz2::bufread::XzDecoder::new_multi_decoder(
+            reader,
+        ))),
+        Compression::Bzip2 => Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(reader))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use super::*;
+
+    const TEXT: &[u8] = b"one\ntwo\nthree\n";
+
+    fn compress(compression: Compression) -> Vec<u8> {
+        match compression {
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(TEXT).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Zstd => zstd::encode_all(TEXT, 0).unwrap(),
+            Compression::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(vec![], 6);
+                encoder.write_all(TEXT).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Bzip2 => {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(vec![], bzip2::Compression::default());
+                encoder.write_all(TEXT).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+
+    #[test]
+    fn 圧縮形式を判定して伸長する() {
+        for compression in [
+            Compression::Gzip,
+            Compression::Zstd,
+            Compression::Xz,
+            Compression::Bzip2,
+        ] {
+            let mut reader = Cursor::new(compress(compression));
+            assert_eq!(detect(&mut reader).unwrap(), Some(compression));
+
+            let mut text = vec![];
+            decoder(compression, reader)
+                .unwrap()
+                .read_to_end(&mut text)
+                .unwrap();
+            assert_eq!(text, TEXT, "{:?}", compression);
+        }
+    }
+
+    #[test]
+    fn 圧縮されていない入力は判定しない() {
+        assert_eq!(detect(&mut Cursor::new(TEXT)).unwrap(), None);
+        assert_eq!(detect(&mut Cursor::new(b"")).unwrap(), None);
+        assert_eq!(
+            detect(&mut Cursor::new(b"BZh9 is my favourite\n")).unwrap(),
+            None
+        );
+        assert_eq!(detect(&mut Cursor::new(b"\x1f\x8bxyz")).unwrap(), None);
+    }
+}
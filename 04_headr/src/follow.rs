@@ -0,0 +1,272 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::unix::fs::MetadataExt,
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+pub(crate) struct FollowOptions {
+    /// ファイル名で追跡し、ローテーションや再作成されたファイルを開き直す (`-F`)
+    pub(crate) by_name: bool,
+    pub(crate) sleep_interval: Duration,
+    pub(crate) pid: Option<i32>,
+    pub(crate) disable_inotify: bool,
+}
+
+/// 追跡中のファイル。`file` が `None` のときは開けていない (`-F` で出現を待っている)
+pub(crate) struct Followed {
+    path: String,
+    file: Option<File>,
+    pos: u64,
+    id: Option<(u64, u64)>,
+}
+
+impl Followed {
+    pub(crate) fn new(path: &str, mut file: File) -> io::Result<Self> {
+        let pos = file.stream_position()?;
+        let id = file_id(&file.metadata()?);
+        Ok(Followed {
+            path: path.to_string(),
+            file: Some(file),
+            pos,
+            id: Some(id),
+        })
+    }
+
+    pub(crate) fn missing(path: &str) -> Self {
+        Followed {
+            path: path.to_string(),
+            file: None,
+            pos: 0,
+            id: None,
+        }
+    }
+
+    /// 切り詰められたファイルは先頭から読み直す
+    fn check_truncation(&mut self) -> io::Result<()> {
+        let Some(file) = &mut self.file else {
+            return Ok(());
+        };
+        if file.metadata()?.len() < self.pos {
+            eprintln!("{}: file truncated", self.path);
+            self.pos = file.seek(SeekFrom::Start(0))?;
+        }
+        Ok(())
+    }
+
+    /// パスの指すファイルが入れ替わっていれば開き直す。開き直したとき true を返す
+    fn check_rotation(&mut self) -> bool {
+        let id = match fs::metadata(&self.path) {
+            Ok(meta) => Some(file_id(&meta)),
+            Err(err) => {
+                if self.file.take().is_some() {
+                    eprintln!("{}: file has become inaccessible: {}", self.path, err);
+                }
+                self.id = None;
+                return false;
+            }
+        };
+        if id == self.id && self.file.is_some() {
+            return false;
+        }
+        let Ok(file) = File::open(&self.path) else {
+            return false;
+        };
+        if self.file.is_some() {
+            eprintln!("{}: file has been replaced; following new file", self.path);
+        } else {
+            eprintln!("{}: file has appeared; following new file", self.path);
+        }
+        self.file = Some(file);
+        self.pos = 0;
+        self.id = id;
+        true
+    }
+}
+
+fn file_id(meta: &fs::Metadata) -> (u64, u64) {
+    (meta.dev(), meta.ino())
+}
+
+fn process_exists(pid: i32) -> bool {
+    // シグナル 0 は送信せずにプロセスの存在だけを確認する
+    let signaled = unsafe { libc::kill(pid, 0) } == 0;
+    signaled || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// 追記されたデータを出力する。出力元のファイルが切り替わったときは見出しを出力する
+struct Printer<W: Write> {
+    out: W,
+    show_headers: bool,
+    last: Option<usize>,
+}
+
+impl<W: Write> Printer<W> {
+    fn print_new_data(&mut self, index: usize, followed: &mut Followed) -> io::Result<()> {
+        let Some(file) = &mut followed.file else {
+            return Ok(());
+        };
+        let mut buf = [0; 8192];
+        loop {
+            let read = file.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            if self.show_headers && self.last != Some(index) {
+                crate::print_header(&mut self.out, &followed.path, false)?;
+            }
+            self.last = Some(index);
+            self.out.write_all(&buf[..read])?;
+            followed.pos += read as u64;
+        }
+        self.out.flush()
+    }
+}
+
+enum Watcher {
+    #[cfg(target_os = "linux")]
+    Inotify(inotify::Inotify),
+    Poll,
+}
+
+impl Watcher {
+    #[cfg(target_os = "linux")]
+    fn new(files: &[Followed], options: &FollowOptions) -> Self {
+        use inotify::{Inotify, WatchMask};
+
+        if options.disable_inotify {
+            return Watcher::Poll;
+        }
+        let Ok(inotify) = Inotify::init() else {
+            return Watcher::Poll;
+        };
+        for followed in files {
+            let path = Path::new(&followed.path);
+            // 存在しないファイルの監視は失敗するが、-F のときは親ディレクトリの監視で出現を検知する
+            let _ = inotify.watches().add(
+                path,
+                WatchMask::MODIFY
+                    | WatchMask::ATTRIB
+                    | WatchMask::DELETE_SELF
+                    | WatchMask::MOVE_SELF,
+            );
+            if options.by_name {
+                let dir = match path.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir,
+                    _ => Path::new("."),
+                };
+                let _ = inotify.watches().add(
+                    dir,
+                    WatchMask::CREATE
+                        | WatchMask::MOVED_TO
+                        | WatchMask::DELETE
+                        | WatchMask::MOVED_FROM,
+                );
+            }
+        }
+        Watcher::Inotify(inotify)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn new(_files: &[Followed], _options: &FollowOptions) -> Self {
+        Watcher::Poll
+    }
+
+    /// 開き直したファイルを監視対象に加える
+    #[cfg(target_os = "linux")]
+    fn rewatch(&mut self, path: &str) {
+        use inotify::WatchMask;
+
+        if let Watcher::Inotify(inotify) = self {
+            let _ = inotify.watches().add(
+                path,
+                WatchMask::MODIFY
+                    | WatchMask::ATTRIB
+                    | WatchMask::DELETE_SELF
+                    | WatchMask::MOVE_SELF,
+            );
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn rewatch(&mut self, _path: &str) {}
+
+    /// 変更の通知があるか、タイムアウトするまで待つ
+    fn wait(&mut self, timeout: Duration) -> io::Result<()> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Watcher::Inotify(inotify) => {
+                use std::os::fd::AsRawFd;
+
+                let mut pollfd = libc::pollfd {
+                    fd: inotify.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+                if unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } < 0 {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                let mut buffer = [0; 4096];
+                loop {
+                    // 変更の有無はファイルごとに確認するので、通知は読み捨てる
+                    match inotify.read_events(&mut buffer) {
+                        Ok(events) => {
+                            if events.count() == 0 {
+                                return Ok(());
+                            }
+                        }
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+            Watcher::Poll => {
+                thread::sleep(timeout);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// ファイルに追記されたデータを出力し続ける。`--pid` のプロセスが終了したら最後の読み込みを行って戻る
+pub(crate) fn follow(
+    mut files: Vec<Followed>,
+    options: &FollowOptions,
+    show_headers: bool,
+    last_printed: Option<usize>,
+    out: impl Write,
+) -> io::Result<()> {
+    let mut printer = Printer {
+        out,
+        show_headers,
+        last: last_printed,
+    };
+    let mut watcher = Watcher::new(&files, options);
+    loop {
+        let alive = options.pid.is_none_or(process_exists);
+        for (i, followed) in files.iter_mut().enumerate() {
+            if let Err(err) = followed.check_truncation() {
+                eprintln!("{}: {}", followed.path, err);
+            }
+            printer.print_new_data(i, followed)?;
+            if options.by_name && followed.check_rotation() {
+                watcher.rewatch(&followed.path);
+                printer.print_new_data(i, followed)?;
+            }
+        }
+        if !alive {
+            return Ok(());
+        }
+        if !options.by_name && files.iter().all(|f| f.file.is_none()) {
+            eprintln!("no files remaining");
+            return Ok(());
+        }
+        watcher.wait(options.sleep_interval)?;
+    }
+}
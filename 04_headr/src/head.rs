@@ -0,0 +1,175 @@
+use std::io::{self, BufRead, Read};
+
+#[derive(Debug, Clone, Copy)]
+enum Limit {
+    Lines(usize),
+    Bytes(u64),
+}
+
+/// 内側のリーダーの先頭 N 行または N バイトだけを読み出すアダプタ
+///
+/// ```
+/// use std::io::{Cursor, Read};
+///
+/// let mut head = headr::HeadReader::lines(Cursor::new("one\ntwo\nthree\n"), 2);
+/// let mut text = String::new();
+/// head.read_to_string(&mut text).unwrap();
+/// assert_eq!(text, "one\ntwo\n");
+/// ```
+#[derive(Debug)]
+pub struct HeadReader<R> {
+    inner: R,
+    limit: Limit,
+    /// 直前の `fill_buf` が改行で終わるスライスを返したときの長さ
+    line_end: Option<usize>,
+}
+
+impl<R: BufRead> HeadReader<R> {
+    pub fn lines(inner: R, n: usize) -> Self {
+        HeadReader {
+            inner,
+            limit: Limit::Lines(n),
+            line_end: None,
+        }
+    }
+
+    pub fn bytes(inner: R, n: u64) -> Self {
+        HeadReader {
+            inner,
+            limit: Limit::Bytes(n),
+            line_end: None,
+        }
+    }
+
+    /// 上限に達したとき true を返す
+    pub fn is_exhausted(&self) -> bool {
+        matches!(self.limit, Limit::Lines(0) | Limit::Bytes(0))
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// 改行コードを含めた行単位で読み出すイテレータに変換する
+    pub fn into_lines(self) -> HeadLines<R> {
+        HeadLines { reader: self }
+    }
+}
+
+impl<R: BufRead> Read for HeadReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for HeadReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.line_end = None;
+        match self.limit {
+            Limit::Lines(0) | Limit::Bytes(0) => Ok(&[]),
+            Limit::Bytes(n) => {
+                let buf = self.inner.fill_buf()?;
+                let len = buf.len().min(usize::try_from(n).unwrap_or(usize::MAX));
+                Ok(&buf[..len])
+            }
+            // 改行を1つまでしか含まないスライスを返し、consume で行数を数えられるようにする
+            Limit::Lines(_) => {
+                let buf = self.inner.fill_buf()?;
+                match buf.iter().position(|&b| b == b'\n') {
+                    Some(i) => {
+                        self.line_end = Some(i + 1);
+                        Ok(&buf[..=i])
+                    }
+                    None => Ok(buf),
+                }
+            }
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match &mut self.limit {
+            Limit::Bytes(n) => *n -= amt as u64,
+            Limit::Lines(n) => {
+                if self.line_end == Some(amt) {
+                    *n -= 1;
+                }
+            }
+        }
+        self.line_end = None;
+        self.inner.consume(amt);
+    }
+}
+
+/// [`HeadReader`] から改行コードを含めた行を読み出すイテレータ
+#[derive(Debug)]
+pub struct HeadLines<R> {
+    reader: HeadReader<R>,
+}
+
+impl<R: BufRead> Iterator for HeadLines<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = Vec::new();
+        match self.reader.read_until(b'\n', &mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(Ok(line)),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use super::*;
+
+    fn read_all(mut reader: impl Read) -> String {
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn 行数を指定したとき_改行コードを保ったまま先頭の行だけを読み出す() {
+        let head = HeadReader::lines(Cursor::new("abc\r\ndef\nghi\n"), 2);
+        assert_eq!(read_all(head), "abc\r\ndef\n");
+    }
+
+    #[test]
+    fn バッファより長い行を数える() {
+        let text = format!("{}\nshort\nrest\n", "x".repeat(100));
+        let head = HeadReader::lines(BufReader::with_capacity(8, Cursor::new(&text)), 2);
+        assert_eq!(read_all(head), format!("{}\nshort\n", "x".repeat(100)));
+    }
+
+    #[test]
+    fn バイト数を指定したとき_先頭のバイトだけを読み出し残りは内側のリーダーに残る() {
+        let mut head = HeadReader::bytes(Cursor::new("Öne line"), 2);
+        assert_eq!(read_all(&mut head), "Ö");
+        assert!(head.is_exhausted());
+        assert_eq!(read_all(head.into_inner()), "ne line");
+    }
+
+    #[test]
+    fn 行イテレータは最終行に改行がなくても行を返す() {
+        let lines = HeadReader::lines(Cursor::new("a\nb"), 5)
+            .into_lines()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines, vec![b"a\n".to_vec(), b"b".to_vec()]);
+    }
+}
@@ -0,0 +1,62 @@
+use serde::Serialize;
+
+/// `--json` のときにファイルごとに出力するオブジェクト
+#[derive(Debug, Serialize)]
+pub(crate) struct FileHead {
+    file: String,
+    lines: Vec<String>,
+    /// 出力した範囲の後にまだ読んでいない内容が残っているか
+    truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl FileHead {
+    /// 出力を改行コードを除いた行に分割する。UTF-8 として不正なバイトは置換文字にする
+    pub(crate) fn new(file: &str, output: &[u8], truncated: bool) -> Self {
+        let lines = output
+            .split_inclusive(|&b| b == b'\n')
+            .map(|line| {
+                let line = line.strip_suffix(b"\n").unwrap_or(line);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                String::from_utf8_lossy(line).into_owned()
+            })
+            .collect();
+        FileHead {
+            file: file.to_string(),
+            lines,
+            truncated,
+            error: None,
+        }
+    }
+
+    pub(crate) fn failed(file: &str, output: &[u8], error: impl ToString) -> Self {
+        FileHead {
+            error: Some(error.to_string()),
+            ..FileHead::new(file, output, false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn 改行コードを除いた行の配列として出力する() {
+        let head = FileHead::new("a.txt", b"one\r\ntwo\n\xffthree", true);
+        assert_eq!(
+            serde_json::to_string(&head).unwrap(),
+            "{\"file\":\"a.txt\",\"lines\":[\"one\",\"two\",\"\u{fffd}three\"],\"truncated\":true}"
+        );
+    }
+
+    #[test]
+    fn エラーがあるときはエラーを含める() {
+        let head = FileHead::failed("missing.txt", b"", "No such file or directory");
+        assert_eq!(
+            serde_json::to_string(&head).unwrap(),
+            r#"{"file":"missing.txt","lines":[],"truncated":false,"error":"No such file or directory"}"#
+        );
+    }
+}
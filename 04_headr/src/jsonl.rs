@@ -0,0 +1,126 @@
+use std::io::{self, BufRead, Write};
+
+use serde_json::{Map, Value};
+
+#[derive(Debug)]
+pub(crate) struct JsonlOptions {
+    pub(crate) n: usize,
+    /// `b.c` のようにドットで区切られたフィールドのパス
+    pub(crate) fields: Vec<String>,
+    pub(crate) pretty: bool,
+}
+
+fn lookup<'a>(value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| match value {
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => value.get(key),
+    })
+}
+
+/// 指定されたフィールドだけを、元の入れ子構造を保ったまま取り出す。存在しないフィールドは含めない
+fn project(value: &Value, fields: &[String]) -> Value {
+    let mut projected = Map::new();
+    for field in fields {
+        let path: Vec<&str> = field.split('.').collect();
+        let Some(found) = lookup(value, &path) else {
+            continue;
+        };
+        let (last, parents) = path.split_last().expect("split always yields a key");
+        let mut target = &mut projected;
+        for key in parents {
+            let entry = target
+                .entry(key.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !entry.is_object() {
+                *entry = Value::Object(Map::new());
+            }
+            target = entry.as_object_mut().expect("inserted as an object");
+        }
+        target.insert(last.to_string(), found.clone());
+    }
+    Value::Object(projected)
+}
+
+/// 先頭の n レコードを JSON として検証して出力する。不正なレコードは行番号とともに標準エラーに報告する
+pub(crate) fn head_jsonl(
+    reader: &mut impl BufRead,
+    filepath: &str,
+    options: &JsonlOptions,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    let mut records = 0;
+    while records < options.n {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        line_number += 1;
+        let record = line.strip_suffix(b"\n").unwrap_or(&line);
+        let record = record.strip_suffix(b"\r").unwrap_or(record);
+        if record.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        records += 1;
+        let value: Value = match serde_json::from_slice(record) {
+            Ok(value) => value,
+            Err(err) => {
+                eprintln!("{}:{}: invalid JSON: {}", filepath, line_number, err);
+                continue;
+            }
+        };
+        if options.fields.is_empty() && !options.pretty {
+            out.write_all(record)?;
+            writeln!(out)?;
+            continue;
+        }
+        let value = if options.fields.is_empty() {
+            value
+        } else {
+            project(&value, &options.fields)
+        };
+        let formatted = if options.pretty {
+            serde_json::to_string_pretty(&value)
+        } else {
+            serde_json::to_string(&value)
+        };
+        writeln!(out, "{}", formatted.map_err(io::Error::other)?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn ドット区切りのフィールドを入れ子のまま取り出す() {
+        let value = json!({"a": 1, "b": {"c": 2, "d": 3}, "e": [{"f": 4}]});
+        let fields = ["b.c", "e.0.f", "a", "missing.x"].map(String::from);
+
+        assert_eq!(
+            project(&value, &fields),
+            json!({"b": {"c": 2}, "e": {"0": {"f": 4}}, "a": 1})
+        );
+    }
+
+    #[test]
+    fn 不正なレコードは出力せず_有効なレコードは元の形のまま出力する() {
+        let input = "{\"b\": 1, \"a\": 2}\nnot json\n\n{\"c\": 3}\n{\"d\": 4}\n";
+        let options = JsonlOptions {
+            n: 3,
+            fields: vec![],
+            pretty: false,
+        };
+        let mut out = vec![];
+        head_jsonl(&mut io::Cursor::new(input), "-", &options, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"b\": 1, \"a\": 2}\n{\"c\": 3}\n"
+        );
+    }
+}
@@ -1,11 +1,38 @@
+mod annotate;
+mod concat;
+mod decompress;
+#[cfg(unix)]
+mod follow;
+mod head;
+mod json;
+mod jsonl;
+mod parallel;
+mod pattern;
+mod records;
+mod sample;
+mod tail;
+mod width;
+
 use std::{
+    cell::Cell,
     fs::File,
-    io::{self, BufRead, BufReader, Read},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
     path::Path,
+    rc::Rc,
+    time::Duration,
+};
+
+use clap::{
+    error::ErrorKind, parser::ValueSource, Command, CommandFactory, FromArgMatches, Parser,
+    ValueEnum,
 };
+use regex::bytes::Regex;
+
+pub use head::{HeadLines, HeadReader};
+pub use records::RecordFormat;
 
-use anyhow::Context;
-use clap::Parser;
+/// `--concat` のときにエラーメッセージなどで入力を指す名前
+const CONCAT_NAME: &str = "(concatenated input)";
 
 fn positive_num(value: &str) -> Result<usize, String> {
     match value.parse::<usize>() {
@@ -15,103 +42,711 @@ fn positive_num(value: &str) -> Result<usize, String> {
     }
 }
 
+/// `1K` や `50G` のような単位付きの数を解釈する。GNU head と同じく `K` は 1024 倍、`KB` は 1000 倍を表す
+fn size(value: &str) -> Result<usize, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, suffix) = value.split_at(split);
+    let n = digits
+        .parse::<usize>()
+        .map_err(|_| "invalid digit found in string".to_string())?;
+    let multiplier = match suffix {
+        "" => 1,
+        "b" => 512,
+        _ => {
+            let mut chars = suffix.chars();
+            let exponent = chars
+                .next()
+                .and_then(|unit| "KMGTPE".find(unit.to_ascii_uppercase()))
+                .map(|i| i as u32 + 1);
+            let base: usize = match chars.as_str() {
+                "" | "iB" => 1024,
+                "B" => 1000,
+                _ => 0,
+            };
+            match exponent {
+                Some(exponent) if base > 0 => base
+                    .checked_pow(exponent)
+                    .ok_or_else(|| format!("Value is too large: {}", value))?,
+                _ => return Err("invalid digit found in string".to_string()),
+            }
+        }
+    };
+    n.checked_mul(multiplier)
+        .ok_or_else(|| format!("Value is too large: {}", value))
+}
+
+/// `-n`/`-c` に指定される数。`+N` の形式はファイルの先頭から数えることを表す。
+/// 0 は末尾から出力するときのみ受け付ける (`parse_args` で検査する)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Count {
+    n: usize,
+    from_start: bool,
+}
+
+fn count(value: &str) -> Result<Count, String> {
+    let (digits, from_start) = match value.strip_prefix('+') {
+        Some(digits) => (digits, true),
+        None => (value, false),
+    };
+    Ok(Count {
+        n: size(digits)?,
+        from_start,
+    })
+}
+
+fn seconds(value: &str) -> Result<Duration, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| format!("invalid number of seconds: '{}'", value))
+}
+
+fn regex(value: &str) -> Result<Regex, String> {
+    Regex::new(value).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl From<CompletionShell> for clap_complete::Shell {
+    fn from(shell: CompletionShell) -> Self {
+        match shell {
+            CompletionShell::Bash => clap_complete::Shell::Bash,
+            CompletionShell::Zsh => clap_complete::Shell::Zsh,
+            CompletionShell::Fish => clap_complete::Shell::Fish,
+        }
+    }
+}
+
 #[derive(Debug, Parser)]
+#[command(version, about = "Rust head", long_about = None)]
 pub struct Args {
-    #[arg(num_args = 1..)]
+    /// Files to read; - means standard input
+    #[arg(value_name = "FILE", default_value = "-")]
     files: Vec<String>,
-    #[arg(short = 'n', long, value_parser = positive_num, conflicts_with = "bytes")]
-    lines: Option<usize>,
-    #[arg(short = 'c', long, value_parser = positive_num, conflicts_with = "lines")]
-    bytes: Option<usize>,
+    /// Print the first N lines instead of the first 10; with --from-end, the last N, or from line N on with +N (N may have a suffix such as K or MB)
+    #[arg(short = 'n', long, value_parser = count, conflicts_with = "bytes")]
+    lines: Option<Count>,
+    /// Print the first N bytes; with --from-end, the last N, or from byte N on with +N (N may have a suffix such as K or MB)
+    #[arg(short = 'c', long, value_parser = count, conflicts_with = "lines")]
+    bytes: Option<Count>,
+    /// Print the end of each file instead of the beginning (like tail)
+    #[arg(long, visible_alias = "tail")]
+    from_end: bool,
+    /// Output appended data as the file grows (implies --from-end)
+    #[arg(short = 'f', long)]
+    follow: bool,
+    /// Like --follow, but reopen files that are rotated, truncated or recreated
+    #[arg(short = 'F')]
+    follow_name: bool,
+    /// With -f, seconds to wait between checks for changes
+    #[arg(short = 's', long, value_parser = seconds, default_value = "1.0")]
+    sleep_interval: Duration,
+    /// With -f, stop following after process PID exits
+    #[arg(long)]
+    pid: Option<i32>,
+    /// Count CSV/TSV records instead of lines, keeping quoted newlines intact
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["bytes", "from_end", "follow", "follow_name"])]
+    records: Option<RecordFormat>,
+    /// With --records, print the header row in addition to the first N records
+    #[arg(long, requires = "records")]
+    keep_header: bool,
+    /// Print N lines chosen at random from the whole input, in input order
+    #[arg(long, value_name = "N", value_parser = positive_num, conflicts_with_all = ["lines", "bytes", "from_end", "follow", "follow_name", "records", "every"])]
+    sample: Option<usize>,
+    /// With --sample, seed the random number generator for reproducible output
+    #[arg(long, requires = "sample")]
+    seed: Option<u64>,
+    /// Print every K-th line starting with the first (up to -n lines if given)
+    #[arg(long, value_name = "K", value_parser = positive_num, conflicts_with_all = ["bytes", "from_end", "follow", "follow_name", "records"])]
+    every: Option<usize>,
+    /// Print lines until one matches REGEX, without the matching line (up to -n lines if given)
+    #[arg(long, value_name = "REGEX", value_parser = regex, conflicts_with_all = ["bytes", "from_end", "follow", "follow_name", "records", "sample", "every", "until_inclusive"])]
+    until: Option<Regex>,
+    /// Like --until, but also print the matching line
+    #[arg(long, value_name = "REGEX", value_parser = regex, conflicts_with_all = ["bytes", "from_end", "follow", "follow_name", "records", "sample", "every"])]
+    until_inclusive: Option<Regex>,
+    /// Start printing after the first line that matches REGEX (up to -n lines if given)
+    #[arg(long, value_name = "REGEX", value_parser = regex, conflicts_with_all = ["bytes", "from_end", "follow", "follow_name", "records", "sample", "every"])]
+    after: Option<Regex>,
+    /// Skip the first OFFSET bytes of each file before printing (seeks on regular files)
+    #[arg(long, value_parser = size, conflicts_with_all = ["from_end", "follow", "follow_name"])]
+    offset: Option<usize>,
+    /// Print one JSON object per file with its lines, whether it was truncated and any error
+    #[arg(long, conflicts_with_all = ["follow", "follow_name"])]
+    json: bool,
+    /// Treat input as JSON Lines: validate the first N records and report invalid ones
+    #[arg(long, conflicts_with_all = ["bytes", "from_end", "follow", "follow_name", "records", "sample", "every", "until", "until_inclusive", "after"])]
+    jsonl: bool,
+    /// With --jsonl, print only these comma-separated fields (nested fields as a.b)
+    #[arg(long, value_delimiter = ',', requires = "jsonl")]
+    fields: Vec<String>,
+    /// With --jsonl, pretty-print each record
+    #[arg(long, requires = "jsonl")]
+    pretty: bool,
+    /// Open and read up to N files concurrently, printing them in argument order
+    #[arg(short = 'j', long, value_name = "N", value_parser = positive_num, default_value = "1", conflicts_with_all = ["follow", "follow_name"])]
+    jobs: usize,
+    /// Truncate each printed line to N display columns (wide characters count as 2)
+    #[arg(long, value_name = "N", value_parser = positive_num)]
+    max_width: Option<usize>,
+    /// With --max-width, mark truncated lines with STRING [default: …]
+    #[arg(long, value_name = "STRING", num_args = 0..=1, require_equals = true, default_missing_value = "…", requires = "max_width", conflicts_with = "wrap")]
+    ellipsis: Option<String>,
+    /// With --max-width, fold long lines instead of truncating them
+    #[arg(long, requires = "max_width")]
+    wrap: bool,
+    /// Treat all inputs as one stream and print the head of their concatenation
+    #[arg(long, conflicts_with_all = ["follow", "follow_name", "jobs", "json"])]
+    concat: bool,
+    /// Always print headers giving file names (with --concat, when each file starts)
+    #[arg(short = 'v', long, conflicts_with = "quiet")]
+    verbose: bool,
+    /// Never print headers giving file names
+    #[arg(short = 'q', long, visible_alias = "silent")]
+    quiet: bool,
+    /// Prefix each printed line with its line number
+    #[arg(long, conflicts_with_all = ["from_end", "follow", "follow_name", "records", "sample", "every", "after", "offset", "jsonl"])]
+    number: bool,
+    /// Prefix each printed line with the byte offset where it starts in the file (in the decompressed data for a compressed file)
+    #[arg(long, conflicts_with_all = ["from_end", "follow", "follow_name", "records", "sample", "every", "after", "jsonl"])]
+    byte_offset: bool,
+    /// Print compressed files as they are instead of decompressing them
+    #[arg(long)]
+    no_decompress: bool,
+    /// Print a completion script for SHELL and exit
+    #[arg(long, value_name = "SHELL", value_enum, exclusive = true)]
+    generate_completions: Option<CompletionShell>,
+    /// Print a man page in roff format and exit
+    #[arg(long, exclusive = true)]
+    generate_man: bool,
+    /// Check for changes by polling instead of using inotify
+    #[arg(long, hide = true)]
+    disable_inotify: bool,
 }
 
 enum Mode {
     Lines(usize),
     Bytes(usize),
+    LastLines(usize),
+    LastBytes(usize),
+    LinesFrom(usize),
+    BytesFrom(usize),
+    Records {
+        format: RecordFormat,
+        n: usize,
+        keep_header: bool,
+    },
+    Sample {
+        n: usize,
+        seed: Option<u64>,
+    },
+    Every {
+        k: usize,
+        limit: Option<usize>,
+    },
+    Pattern(pattern::Bounds),
+    Jsonl(jsonl::JsonlOptions),
 }
 
 impl Args {
+    fn show_headers(&self) -> bool {
+        !self.quiet && (self.verbose || self.files.len() > 1)
+    }
+
+    fn is_follow(&self) -> bool {
+        self.follow || self.follow_name
+    }
+
     fn mode(&self) -> Mode {
+        if let Some(format) = self.records {
+            return Mode::Records {
+                format,
+                n: self.lines.map_or(10, |lines| lines.n),
+                keep_header: self.keep_header,
+            };
+        }
+        if self.jsonl {
+            return Mode::Jsonl(jsonl::JsonlOptions {
+                n: self.lines.map_or(10, |lines| lines.n),
+                fields: self.fields.clone(),
+                pretty: self.pretty,
+            });
+        }
+        if let Some(n) = self.sample {
+            return Mode::Sample { n, seed: self.seed };
+        }
+        if let Some(k) = self.every {
+            return Mode::Every {
+                k,
+                limit: self.lines.map(|lines| lines.n),
+            };
+        }
+        if self.until.is_some() || self.until_inclusive.is_some() || self.after.is_some() {
+            return Mode::Pattern(pattern::Bounds {
+                after: self.after.clone(),
+                until: self.until.clone().or_else(|| self.until_inclusive.clone()),
+                inclusive: self.until_inclusive.is_some(),
+                limit: self.lines.map(|lines| lines.n),
+            });
+        }
+        let from_end = self.from_end || self.is_follow();
         if let Some(bytes) = self.bytes {
-            Mode::Bytes(bytes)
+            match (from_end, bytes.from_start) {
+                (false, _) => Mode::Bytes(bytes.n),
+                (true, false) => Mode::LastBytes(bytes.n),
+                (true, true) => Mode::BytesFrom(bytes.n),
+            }
         } else {
-            Mode::Lines(self.lines.unwrap_or(10))
+            let lines = self.lines.unwrap_or(Count {
+                n: 10,
+                from_start: false,
+            });
+            match (from_end, lines.from_start) {
+                (false, _) => Mode::Lines(lines.n),
+                (true, false) => Mode::LastLines(lines.n),
+                (true, true) => Mode::LinesFrom(lines.n),
+            }
         }
     }
 }
 
-pub fn get_args() -> Args {
-    let args = Args::parse();
+/// 先頭から読むときだけ意味を持つ引数。`tailr` では受け付けない
+const HEAD_ONLY: [&str; 14] = [
+    "records",
+    "keep_header",
+    "sample",
+    "seed",
+    "every",
+    "until",
+    "until_inclusive",
+    "after",
+    "offset",
+    "jsonl",
+    "fields",
+    "pretty",
+    "number",
+    "byte_offset",
+];
+
+/// 引数を解釈する。補完スクリプトや man ページの生成が指定されたときは出力して終了する。
+/// `tail` のときは常に末尾から出力する
+fn parse_args(mut command: Command, tail: bool) -> Args {
+    let matches = command.clone().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if tail {
+        // 解析後に設定する from_end には clap の conflicts_with が働かないので、ここで拒否する
+        let head_only = command
+            .get_arguments()
+            .filter(|arg| HEAD_ONLY.contains(&arg.get_id().as_str()))
+            .find(|arg| {
+                matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+            });
+        if let Some(arg) = head_only {
+            let message = format!(
+                "the argument '--{}' cannot be used with {}",
+                arg.get_long().unwrap_or_default(),
+                command.get_name()
+            );
+            command.error(ErrorKind::ArgumentConflict, message).exit();
+        }
+        args.from_end = true;
+    }
+    // 出力をためるモードでは、書き込むときに読んでいる入力が出力する行の出どころと限らず、見出しを付けられない
+    if args.concat && args.verbose {
+        let buffering = [
+            ("'--from-end'", args.from_end),
+            ("'--sample'", args.sample.is_some()),
+            ("'--jsonl'", args.jsonl),
+        ]
+        .into_iter()
+        .find(|(_, used)| *used);
+        if let Some((name, _)) = buffering {
+            let with = if tail {
+                format!("in {}", command.get_name())
+            } else {
+                format!("and {}", name)
+            };
+            let message = format!(
+                "the argument '--verbose' cannot be used with '--concat' {}",
+                with
+            );
+            command.error(ErrorKind::ArgumentConflict, message).exit();
+        }
+    }
+    if !args.from_end && !args.is_follow() {
+        let zero = [
+            ("--lines <LINES>", args.lines),
+            ("--bytes <BYTES>", args.bytes),
+        ]
+        .into_iter()
+        .find(|(_, count)| count.is_some_and(|count| count.n == 0));
+        if let Some((name, _)) = zero {
+            let message = format!(
+                "invalid value '0' for '{}': Value must be a positive integer, found: 0",
+                name
+            );
+            command.error(ErrorKind::ValueValidation, message).exit();
+        }
+    }
+    if let Some(shell) = args.generate_completions {
+        let name = command.get_name().to_string();
+        clap_complete::generate(
+            clap_complete::Shell::from(shell),
+            &mut command,
+            name,
+            &mut io::stdout(),
+        );
+        std::process::exit(0);
+    }
+    if args.generate_man {
+        clap_mangen::Man::new(command)
+            .render(&mut io::stdout())
+            .expect("failed to write to stdout");
+        std::process::exit(0);
+    }
     args
 }
 
-fn open(filepath: impl AsRef<Path>) -> anyhow::Result<Box<dyn BufRead>> {
-    let filepath = filepath.as_ref();
-    match filepath.to_str() {
-        Some("-") => {
-            let stdin = io::stdin();
-            Ok(Box::new(BufReader::new(stdin.lock())))
+pub fn get_args() -> Args {
+    parse_args(Args::command(), false)
+}
+
+/// `tailr` として起動されたときの引数。常に末尾から出力する
+pub fn get_tail_args() -> Args {
+    // 先頭から読むときだけの引数は受け付けないので、ヘルプにも出さない
+    let command = HEAD_ONLY
+        .into_iter()
+        .chain(["from_end"])
+        .fold(Args::command(), |command, id| {
+            command.mut_arg(id, |arg| arg.hide(true))
+        })
+        .name("tailr")
+        .about("Rust tail")
+        .mut_arg("lines", |arg| {
+            arg.help("Print the last N lines instead of the last 10, or from line N on with +N (N may have a suffix such as K or MB)")
+        })
+        .mut_arg("bytes", |arg| {
+            arg.help("Print the last N bytes, or from byte N on with +N (N may have a suffix such as K or MB)")
+        })
+        .mut_arg("follow", |arg| {
+            arg.help("Output appended data as the file grows")
+        })
+        .mut_arg("concat", |arg| {
+            arg.help("Treat all inputs as one stream and print the end of their concatenation")
+        });
+    parse_args(command, true)
+}
+
+enum Input {
+    Stdin(io::StdinLock<'static>),
+    File(BufReader<File>),
+    /// 伸長しながら読む入力や、連結した入力のようなシークできないストリーム
+    Stream(Box<dyn BufRead>),
+}
+
+impl Input {
+    /// 通常ファイルのときのみシーク可能なハンドルを返す
+    fn seekable(&mut self) -> Option<&mut BufReader<File>> {
+        match self {
+            Input::File(file) if file.get_ref().metadata().is_ok_and(|m| m.is_file()) => Some(file),
+            _ => None,
+        }
+    }
+
+    /// 先頭の n バイトを読み飛ばす。通常ファイルは読まずにシークする
+    fn skip(&mut self, n: u64) -> io::Result<()> {
+        match self.seekable() {
+            Some(file) => file.seek(SeekFrom::Start(n)).map(|_| ()),
+            None => io::copy(&mut self.by_ref().take(n), &mut io::sink()).map(|_| ()),
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Stdin(stdin) => stdin.read(buf),
+            Input::File(file) => file.read(buf),
+            Input::Stream(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl BufRead for Input {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Input::Stdin(stdin) => stdin.fill_buf(),
+            Input::File(file) => file.fill_buf(),
+            Input::Stream(stream) => stream.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            Input::Stdin(stdin) => stdin.consume(amt),
+            Input::File(file) => file.consume(amt),
+            Input::Stream(stream) => stream.consume(amt),
         }
+    }
+}
+
+/// `decompress` のときは gzip、zstd、xz、bzip2 で圧縮された入力をマジックバイトで判定して伸長する
+fn open(filepath: impl AsRef<Path>, decompress: bool) -> anyhow::Result<Input> {
+    let filepath = filepath.as_ref();
+    let mut input = match filepath.to_str() {
+        Some("-") => Input::Stdin(io::stdin().lock()),
         _ => {
             let file = File::open(filepath)?;
-            Ok(Box::new(BufReader::new(file)))
+            Input::File(BufReader::new(file))
+        }
+    };
+    if decompress {
+        if let Some(compression) = decompress::detect(&mut input)? {
+            return Ok(Input::Stream(decompress::decoder(compression, input)?));
         }
     }
+    Ok(input)
 }
 
-fn head_bytes(file: &mut impl BufRead, n: usize) -> Result<String, anyhow::Error> {
-    let mut buf = vec![0; n];
-    let read = file
-        .take(n as u64)
-        .read(&mut buf)
-        .context("Failed to read")?;
-    Ok(String::from_utf8_lossy(&buf[..read]).into())
+/// 複数ファイルを出力するときの `==> name <==` 見出しを出力する
+fn print_header(out: &mut impl Write, filepath: &str, is_first: bool) -> io::Result<()> {
+    if !is_first {
+        writeln!(out)?;
+    }
+    writeln!(out, "==> {} <==", filepath)
+}
+
+fn print_file(
+    file: &mut Input,
+    filepath: &str,
+    mode: &Mode,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    match *mode {
+        Mode::Lines(n) => io::copy(&mut HeadReader::lines(file, n), out).map(|_| ()),
+        Mode::Bytes(n) => io::copy(&mut HeadReader::bytes(file, n as u64), out).map(|_| ()),
+        Mode::LastLines(n) => match file.seekable() {
+            Some(seekable) => tail::last_lines_seek(seekable, n, out),
+            None => tail::last_lines(file, n, out),
+        },
+        Mode::LastBytes(n) => match file.seekable() {
+            Some(seekable) => tail::last_bytes_seek(seekable, n, out),
+            None => tail::last_bytes(file, n, out),
+        },
+        Mode::LinesFrom(n) => tail::lines_from(file, n, out),
+        Mode::BytesFrom(n) => tail::bytes_from(file, n, out),
+        Mode::Records {
+            format,
+            n,
+            keep_header,
+        } => records::head_records(file, format, n, keep_header, out),
+        Mode::Sample { n, seed } => sample::sample_lines(file, n, seed, out),
+        Mode::Every { k, limit } => sample::every_lines(file, k, limit, out),
+        Mode::Pattern(ref bounds) => pattern::head_between(file, bounds, out),
+        Mode::Jsonl(ref options) => jsonl::head_jsonl(file, filepath, options, out),
+    }
 }
 
-fn head_lines(file: &mut impl BufRead, n: u64) {
-    let mut line = String::new();
-    for _ in 0..n {
-        let read = match file.read_line(&mut line) {
-            Ok(read) => read,
-            Err(_) => {
-                eprintln!("error: unexpected error");
-                return;
+/// `--offset` を読み飛ばしてから、モードに応じた内容を出力する
+fn process(
+    file: &mut Input,
+    filepath: &str,
+    args: &Args,
+    mode: &Mode,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    if let Some(offset) = args.offset {
+        file.skip(offset as u64)?;
+    }
+    match args.max_width {
+        Some(max_width) => {
+            let overflow = if args.wrap {
+                width::Overflow::Wrap
+            } else {
+                width::Overflow::Truncate {
+                    ellipsis: args.ellipsis.clone(),
+                }
+            };
+            let mut limited = width::WidthLimiter::new(out, max_width, overflow);
+            annotate(file, filepath, args, mode, &mut limited)?;
+            limited.finish()
+        }
+        None => annotate(file, filepath, args, mode, out),
+    }
+}
+
+/// `--number`/`--byte-offset` のとき、出力する各行の先頭に行番号やオフセットを付ける
+fn annotate(
+    file: &mut Input,
+    filepath: &str,
+    args: &Args,
+    mode: &Mode,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    if !args.number && !args.byte_offset {
+        return print_file(file, filepath, mode, out);
+    }
+    let start = args.offset.unwrap_or(0) as u64;
+    let mut annotated = annotate::Annotator::new(out, args.number, args.byte_offset, start);
+    print_file(file, filepath, mode, &mut annotated)
+}
+
+fn run_json(args: &Args, mode: &Mode) {
+    parallel::for_each_ordered(
+        &args.files,
+        args.jobs,
+        |filepath| {
+            let mut output = vec![];
+            match open(filepath, !args.no_decompress) {
+                Err(err) => json::FileHead::failed(filepath, &output, err),
+                Ok(mut file) => match process(&mut file, filepath, args, mode, &mut output)
+                    .and_then(|_| file.fill_buf().map(|rest| !rest.is_empty()))
+                {
+                    Ok(truncated) => json::FileHead::new(filepath, &output, truncated),
+                    Err(err) => json::FileHead::failed(filepath, &output, err),
+                },
             }
-        };
-        if read == 0 {
-            break;
+        },
+        |_, head| {
+            println!(
+                "{}",
+                serde_json::to_string(&head).expect("failed to serialize")
+            );
+        },
+    );
+}
+
+/// `--jobs` のとき、各ファイルの出力をいったんバッファにためてから引数の順に出力する
+fn run_parallel(args: &Args, mode: &Mode) {
+    parallel::for_each_ordered(
+        &args.files,
+        args.jobs,
+        |filepath| {
+            let mut output = vec![];
+            let result = open(filepath, !args.no_decompress)
+                .map_err(|err| err.to_string())
+                .and_then(|mut file| {
+                    process(&mut file, filepath, args, mode, &mut output)
+                        .map_err(|err| err.to_string())
+                });
+            (output, result.err())
+        },
+        |i, (output, error)| {
+            let filepath = &args.files[i];
+            let mut stdout = io::stdout().lock();
+            let written = if args.show_headers() {
+                print_header(&mut stdout, filepath, i == 0)
+            } else {
+                Ok(())
+            };
+            written
+                .and_then(|_| stdout.write_all(&output))
+                .expect("failed to write to stdout");
+            if let Some(err) = error {
+                eprintln!("{}: {}", filepath, err);
+            }
+        },
+    );
+}
+
+/// すべての入力を連結した1つのストリームとして出力する
+fn run_concat(args: &Args, mode: &Mode) {
+    let switched = args.verbose.then(|| Rc::new(Cell::new(None)));
+    let stream = concat::Concat::new(args.files.clone(), !args.no_decompress, switched.clone());
+    let mut input = Input::Stream(Box::new(stream));
+    let stdout = io::stdout().lock();
+    let result = match switched {
+        Some(switched) => {
+            let mut out = concat::HeaderWriter::new(stdout, &args.files, switched);
+            process(&mut input, CONCAT_NAME, args, mode, &mut out)
         }
-        print!("{}", line);
-        line.clear();
+        None => process(&mut input, CONCAT_NAME, args, mode, &mut { stdout }),
+    };
+    if let Err(err) = result {
+        eprintln!("{}: {}", CONCAT_NAME, err);
     }
 }
 
 pub fn run(args: Args) {
-    let file_count = args.files.len();
+    let mode = args.mode();
+    if args.json {
+        run_json(&args, &mode);
+        return;
+    }
+    if args.jobs > 1 {
+        run_parallel(&args, &mode);
+        return;
+    }
+    if args.concat {
+        run_concat(&args, &mode);
+        return;
+    }
+    #[cfg(unix)]
+    let mut followed = vec![];
+    // 最後に見出しを出力したファイルの `followed` での位置 (追跡しないファイルなら None)
+    #[cfg(unix)]
+    let mut last_printed = None;
     for (i, filepath) in args.files.iter().enumerate() {
-        if file_count > 1 {
-            println!("==> {} <==", filepath);
+        #[cfg(unix)]
+        {
+            last_printed = None;
+        }
+        if args.show_headers() {
+            print_header(&mut io::stdout(), filepath, i == 0).expect("failed to write to stdout");
         }
-        let mut file = match open(&filepath) {
+        let mut file = match open(filepath, !args.no_decompress) {
             Ok(file) => file,
-            Err(_) => {
-                eprint!("{}: .* (os error 2)", filepath);
-                return;
+            Err(err) => {
+                eprintln!("{}: {}", filepath, err);
+                #[cfg(unix)]
+                if args.follow_name {
+                    followed.push(follow::Followed::missing(filepath));
+                    last_printed = Some(followed.len() - 1);
+                }
+                continue;
             }
         };
 
-        match &args.mode() {
-            Mode::Lines(n) => {
-                head_lines(&mut file, *n as u64);
-            }
-            Mode::Bytes(n) => {
-                let haeded = head_bytes(&mut file, *n).unwrap();
-                print!("{}", haeded);
+        if let Err(err) = process(&mut file, filepath, &args, &mode, &mut io::stdout().lock()) {
+            eprintln!("{}: {}", filepath, err);
+        }
+
+        #[cfg(unix)]
+        if let (true, Input::File(file)) = (args.is_follow(), file) {
+            match follow::Followed::new(filepath, file.into_inner()) {
+                Ok(f) => {
+                    followed.push(f);
+                    last_printed = Some(followed.len() - 1);
+                }
+                Err(err) => eprintln!("{}: {}", filepath, err),
             }
         }
-        let is_last = i == file_count - 1;
-        if file_count > 1 && !is_last {
-            println!();
+    }
+
+    #[cfg(unix)]
+    if args.is_follow() {
+        let options = follow::FollowOptions {
+            by_name: args.follow_name,
+            sleep_interval: args.sleep_interval,
+            pid: args.pid,
+            disable_inotify: args.disable_inotify,
+        };
+        if let Err(err) = follow::follow(
+            followed,
+            &options,
+            args.show_headers(),
+            last_printed,
+            io::stdout().lock(),
+        ) {
+            eprintln!("error: {}", err);
         }
     }
 }
@@ -119,24 +754,35 @@ pub fn run(args: Args) {
 #[cfg(test)]
 mod tests {
 
-    use tempfile::tempdir;
+    use std::io::Cursor;
 
     use super::*;
 
-    // TODO: ファイルアクセスを行わないテストへ変更する
     #[test]
     fn バイト数オプション_ファイルのバイト数より指定されたバイト数のが多いとき_ファイルのデータをすべて出力する(
     ) {
         // Arrange
-        let dir = tempdir().unwrap();
-        let filepath = dir.path().join("one.txt");
-        std::fs::write(&filepath, "Öne line, four words.\n").unwrap();
+        let mut file = Cursor::new("Öne line, four words.\n");
 
         // Act
-        let mut file = std::io::BufReader::new(std::fs::File::open(&filepath).unwrap());
-        let actual = head_bytes(&mut file, 100).unwrap();
+        let mut actual = String::new();
+        HeadReader::bytes(&mut file, 100)
+            .read_to_string(&mut actual)
+            .unwrap();
 
         // Assert
         assert_eq!(actual, "Öne line, four words.\n");
     }
+
+    #[test]
+    fn 単位付きの数を解釈する() {
+        assert_eq!(size("12"), Ok(12));
+        assert_eq!(size("2b"), Ok(1024));
+        assert_eq!(size("1K"), Ok(1024));
+        assert_eq!(size("1kB"), Ok(1000));
+        assert_eq!(size("3MiB"), Ok(3 * 1024 * 1024));
+        assert_eq!(size("50G"), Ok(50 * 1024 * 1024 * 1024));
+        assert!(size("1X").is_err());
+        assert!(size("99999999999E").is_err());
+    }
 }
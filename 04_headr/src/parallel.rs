@@ -0,0 +1,128 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Condvar, Mutex,
+    },
+    thread,
+};
+
+/// 渡し終えた結果の数と、それが増えたことの通知
+type Emitted = (Mutex<usize>, Condvar);
+
+/// 出力側が抜けたとき (パニックを含む) に、待っているスレッドをすべて先へ進ませる
+struct Release<'a>(&'a Emitted);
+
+impl Drop for Release<'_> {
+    fn drop(&mut self) {
+        let (count, advanced) = self.0;
+        *count.lock().unwrap_or_else(|e| e.into_inner()) = usize::MAX;
+        advanced.notify_all();
+    }
+}
+
+/// `items` を最大 `jobs` 個のスレッドで `work` に渡し、結果を `items` の順に `emit` へ渡す
+///
+/// 先に終わった結果は、それより前の要素の結果がそろうまで保持しておく。
+/// 保持する結果が増え続けないよう、まだ渡していない最初の要素から `jobs` 個先までしか取りかからない
+pub(crate) fn for_each_ordered<T: Send>(
+    items: &[String],
+    jobs: usize,
+    work: impl Fn(&str) -> T + Sync,
+    mut emit: impl FnMut(usize, T),
+) {
+    let jobs = jobs.clamp(1, items.len().max(1));
+    let next = AtomicUsize::new(0);
+    let emitted: Emitted = (Mutex::new(0), Condvar::new());
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let tx = tx.clone();
+            let (next, work, emitted) = (&next, &work, &emitted);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= items.len() {
+                    break;
+                }
+                let (count, advanced) = emitted;
+                drop(
+                    advanced
+                        .wait_while(count.lock().unwrap(), |count| {
+                            i >= count.saturating_add(jobs)
+                        })
+                        .unwrap(),
+                );
+                if tx.send((i, work(&items[i]))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+        let _release = Release(&emitted);
+
+        let mut pending = BTreeMap::new();
+        let mut expected = 0;
+        for (i, result) in rx {
+            pending.insert(i, result);
+            while let Some(result) = pending.remove(&expected) {
+                emit(expected, result);
+                expected += 1;
+            }
+            let (count, advanced) = &emitted;
+            *count.lock().unwrap() = expected;
+            advanced.notify_all();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn 処理の終わる順序によらず入力の順に結果を渡す() {
+        let items: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let mut emitted = vec![];
+        for_each_ordered(
+            &items,
+            4,
+            |item| {
+                let n: u64 = item.parse().unwrap();
+                // 後の要素ほど先に終わるようにする
+                thread::sleep(Duration::from_millis(20 - n));
+                n
+            },
+            |i, n| emitted.push((i, n)),
+        );
+
+        assert_eq!(emitted, (0..20).map(|i| (i, i as u64)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn 先頭の要素が遅いとき_並列数より先の要素には取りかからない() {
+        let items: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let started = Mutex::new(vec![]);
+        let mut started_before_first = vec![];
+        for_each_ordered(
+            &items,
+            3,
+            |item| {
+                let n: usize = item.parse().unwrap();
+                started.lock().unwrap().push(n);
+                if n == 0 {
+                    thread::sleep(Duration::from_millis(100));
+                }
+            },
+            |i, ()| {
+                if i == 0 {
+                    started_before_first = started.lock().unwrap().clone();
+                }
+            },
+        );
+
+        started_before_first.sort();
+        assert_eq!(started_before_first, [0, 1, 2]);
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::{self, BufRead, Write};
+
+use regex::bytes::Regex;
+
+/// 正規表現にマッチする行で出力の開始・終了を決める条件
+#[derive(Debug)]
+pub(crate) struct Bounds {
+    /// マッチした行の次の行から出力を始める
+    pub(crate) after: Option<Regex>,
+    /// マッチした行で出力を終える
+    pub(crate) until: Option<Regex>,
+    /// `until` にマッチした行も出力する
+    pub(crate) inclusive: bool,
+    /// 出力する行数の上限
+    pub(crate) limit: Option<usize>,
+}
+
+fn strip_line_ending(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+pub(crate) fn head_between(
+    reader: &mut impl BufRead,
+    bounds: &Bounds,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut started = bounds.after.is_none();
+    let mut printed = 0;
+    let mut line = Vec::new();
+    while bounds.limit.is_none_or(|limit| printed < limit) {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let text = strip_line_ending(&line);
+        if !started {
+            started = bounds.after.as_ref().is_some_and(|re| re.is_match(text));
+            continue;
+        }
+        if bounds.until.as_ref().is_some_and(|re| re.is_match(text)) {
+            if bounds.inclusive {
+                out.write_all(&line)?;
+            }
+            break;
+        }
+        out.write_all(&line)?;
+        printed += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const LOG: &str = "# preamble\r\n# more\r\n---\r\nbody 1\r\nbody 2\r\n---\r\ntrailer\r\n";
+
+    fn head(
+        after: Option<&str>,
+        until: Option<&str>,
+        inclusive: bool,
+        limit: Option<usize>,
+    ) -> String {
+        let bounds = Bounds {
+            after: after.map(|re| Regex::new(re).unwrap()),
+            until: until.map(|re| Regex::new(re).unwrap()),
+            inclusive,
+            limit,
+        };
+        let mut out = vec![];
+        head_between(&mut Cursor::new(LOG), &bounds, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn マッチする行の手前まで出力する() {
+        assert_eq!(
+            head(None, Some("^---$"), false, None),
+            "# preamble\r\n# more\r\n"
+        );
+        assert_eq!(
+            head(None, Some("^---$"), true, None),
+            "# preamble\r\n# more\r\n---\r\n"
+        );
+    }
+
+    #[test]
+    fn 開始と終了の両方を指定したとき_その間の行を出力する() {
+        assert_eq!(
+            head(Some("^---$"), Some("^---$"), false, None),
+            "body 1\r\nbody 2\r\n"
+        );
+    }
+
+    #[test]
+    fn 行数の上限に達したとき_マッチする行がなくても出力を終える() {
+        assert_eq!(
+            head(Some("^---$"), Some("^none$"), false, Some(1)),
+            "body 1\r\n"
+        );
+    }
+}
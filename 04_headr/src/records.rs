@@ -0,0 +1,133 @@
+use std::io::{self, BufRead, Write};
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RecordFormat {
+    Csv,
+    Tsv,
+}
+
+impl RecordFormat {
+    fn delimiter(self) -> u8 {
+        match self {
+            RecordFormat::Csv => b',',
+            RecordFormat::Tsv => b'\t',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    FieldStart,
+    Unquoted,
+    Quoted,
+    /// 引用符で囲まれたフィールドの中で `"` を読んだ直後。`""` のエスケープか、引用の終わり
+    QuoteInQuoted,
+}
+
+/// 引用符の中の改行をまたいで、1レコード分のバイト列を改行コードも含めて `buf` に読み込む
+fn read_record(
+    reader: &mut impl BufRead,
+    format: RecordFormat,
+    buf: &mut Vec<u8>,
+) -> io::Result<usize> {
+    let delimiter = format.delimiter();
+    let mut state = State::FieldStart;
+    let mut read = 0;
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Ok(read);
+        }
+        let mut end = None;
+        for (i, &b) in available.iter().enumerate() {
+            state = match (state, b) {
+                (State::Quoted, b'"') => State::QuoteInQuoted,
+                (State::Quoted, _) => State::Quoted,
+                (State::FieldStart, b'"') | (State::QuoteInQuoted, b'"') => State::Quoted,
+                (_, b'\n') => {
+                    end = Some(i + 1);
+                    break;
+                }
+                (_, b) if b == delimiter => State::FieldStart,
+                _ => State::Unquoted,
+            };
+        }
+        let used = end.unwrap_or(available.len());
+        buf.extend_from_slice(&available[..used]);
+        reader.consume(used);
+        read += used;
+        if end.is_some() {
+            return Ok(read);
+        }
+    }
+}
+
+/// 先頭の n レコードを出力する。`keep_header` のときはヘッダー行に加えて n レコードを出力する
+pub(crate) fn head_records(
+    reader: &mut impl BufRead,
+    format: RecordFormat,
+    n: usize,
+    keep_header: bool,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut record = Vec::new();
+    let count = if keep_header { n + 1 } else { n };
+    for _ in 0..count {
+        record.clear();
+        if read_record(reader, format, &mut record)? == 0 {
+            break;
+        }
+        out.write_all(&record)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use super::*;
+
+    const CSV: &str = "id,note\n1,\"multi\nline\"\n2,\"say \"\"hi\"\"\nthere\"\n3,plain\n";
+
+    fn head(text: &str, format: RecordFormat, n: usize, keep_header: bool) -> String {
+        let mut out = vec![];
+        // 小さいバッファでレコードがバッファをまたぐ場合も確認する
+        let mut reader = BufReader::with_capacity(4, Cursor::new(text));
+        head_records(&mut reader, format, n, keep_header, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn 引用符の中の改行はレコードの区切りとして数えない() {
+        assert_eq!(
+            head(CSV, RecordFormat::Csv, 3, false),
+            "id,note\n1,\"multi\nline\"\n2,\"say \"\"hi\"\"\nthere\"\n"
+        );
+    }
+
+    #[test]
+    fn ヘッダーを残すとき_ヘッダーに加えて指定された数のレコードを出力する() {
+        assert_eq!(
+            head(CSV, RecordFormat::Csv, 1, true),
+            "id,note\n1,\"multi\nline\"\n"
+        );
+    }
+
+    #[test]
+    fn フィールドの途中の引用符は引用の開始とみなさない() {
+        let text = "a,b\"c\nd,e\n";
+        assert_eq!(head(text, RecordFormat::Csv, 1, false), "a,b\"c\n");
+    }
+
+    #[test]
+    fn tsvではタブの直後の引用符を引用の開始とみなす() {
+        let text = "a\t\"b\nc\"\nd,\"e\nf\n";
+        assert_eq!(
+            head(text, RecordFormat::Tsv, 2, false),
+            "a\t\"b\nc\"\nd,\"e\n"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::{self, BufRead, Write};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// 入力全体から n 行を一様に選んで、入力での順序のまま出力する (リザーバサンプリング)
+pub(crate) fn sample_lines(
+    reader: &mut impl BufRead,
+    n: usize,
+    seed: Option<u64>,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let mut reservoir: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut line = Vec::new();
+    let mut index = 0;
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        if reservoir.len() < n {
+            reservoir.push((index, std::mem::take(&mut line)));
+        } else {
+            let j = rng.gen_range(0..=index);
+            if j < n {
+                reservoir[j] = (index, std::mem::take(&mut line));
+            }
+        }
+        index += 1;
+        line.clear();
+    }
+    reservoir.sort_by_key(|(index, _)| *index);
+    for (_, line) in reservoir {
+        out.write_all(&line)?;
+    }
+    Ok(())
+}
+
+/// 1行目から k 行ごとに出力する。`limit` が指定されたときはその行数で打ち切る
+pub(crate) fn every_lines(
+    reader: &mut impl BufRead,
+    k: usize,
+    limit: Option<usize>,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut line = Vec::new();
+    let mut index = 0;
+    let mut printed = 0;
+    while limit.is_none_or(|limit| printed < limit) {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if index % k == 0 {
+            out.write_all(&line)?;
+            printed += 1;
+        }
+        index += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn numbered(count: usize) -> String {
+        (1..=count).map(|i| format!("{i}\n")).collect()
+    }
+
+    fn sample(text: &str, n: usize, seed: u64) -> Vec<usize> {
+        let mut out = vec![];
+        sample_lines(&mut Cursor::new(text), n, Some(seed), &mut out).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn 同じシードのとき_同じ行が入力の順序で選ばれる() {
+        let text = numbered(1000);
+        let first = sample(&text, 5, 42);
+
+        assert_eq!(first, sample(&text, 5, 42));
+        assert_eq!(first.len(), 5);
+        assert!(first.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn 入力の行数が標本数より少ないとき_すべての行を出力する() {
+        assert_eq!(sample(&numbered(3), 5, 0), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn 指定された間隔ごとに行を出力する() {
+        let mut all = vec![];
+        every_lines(&mut Cursor::new(numbered(10)), 3, None, &mut all).unwrap();
+        let mut limited = vec![];
+        every_lines(&mut Cursor::new(numbered(10)), 3, Some(2), &mut limited).unwrap();
+
+        assert_eq!(all, b"1\n4\n7\n10\n");
+        assert_eq!(limited, b"1\n4\n");
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, Read, Seek, SeekFrom, Write},
+};
+
+const BLOCK_SIZE: usize = 8192;
+
+/// ファイル末尾からブロック単位で読み戻し、末尾 n 行を出力する
+pub(crate) fn last_lines_seek<R: Read + Seek>(
+    reader: &mut R,
+    n: usize,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if n == 0 {
+        return Ok(());
+    }
+    let mut buf = vec![0; BLOCK_SIZE];
+    let mut pos = len;
+    let mut start = 0;
+    let mut newlines = 0;
+    'search: while pos > 0 {
+        let size = pos.min(BLOCK_SIZE as u64) as usize;
+        pos -= size as u64;
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut buf[..size])?;
+        for i in (0..size).rev() {
+            let offset = pos + i as u64;
+            // 末尾の改行は最終行の終端なので区切りとして数えない
+            if buf[i] != b'\n' || offset + 1 == len {
+                continue;
+            }
+            newlines += 1;
+            if newlines == n {
+                start = offset + 1;
+                break 'search;
+            }
+        }
+    }
+    reader.seek(SeekFrom::Start(start))?;
+    io::copy(reader, out)?;
+    Ok(())
+}
+
+/// シークできない入力に対して、直近 n 行をリングバッファに保持しながら読み進める
+pub(crate) fn last_lines(
+    reader: &mut impl BufRead,
+    n: usize,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut lines: VecDeque<Vec<u8>> = VecDeque::new();
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        lines.push_back(line);
+        // 押し出された行のバッファは次の行の読み込みに再利用する
+        line = if lines.len() > n {
+            lines.pop_front().unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        line.clear();
+    }
+    for line in lines {
+        out.write_all(&line)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn last_bytes_seek<R: Read + Seek>(
+    reader: &mut R,
+    n: usize,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(len.saturating_sub(n as u64)))?;
+    io::copy(reader, out)?;
+    Ok(())
+}
+
+pub(crate) fn last_bytes(reader: &mut impl Read, n: usize, out: &mut impl Write) -> io::Result<()> {
+    let mut ring: VecDeque<u8> = VecDeque::new();
+    let mut buf = vec![0; BLOCK_SIZE];
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        ring.extend(&buf[..read]);
+        if ring.len() > n {
+            ring.drain(..ring.len() - n);
+        }
+    }
+    let (front, back) = ring.as_slices();
+    out.write_all(front)?;
+    out.write_all(back)?;
+    Ok(())
+}
+
+/// n 行目から最後までを出力する
+pub(crate) fn lines_from(
+    reader: &mut impl BufRead,
+    n: usize,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    for _ in 1..n {
+        if reader.skip_until(b'\n')? == 0 {
+            return Ok(());
+        }
+    }
+    io::copy(reader, out)?;
+    Ok(())
+}
+
+/// n バイト目から最後までを出力する。GNU tail と同じく `+0` は `+1` と同じ
+pub(crate) fn bytes_from(reader: &mut impl Read, n: usize, out: &mut impl Write) -> io::Result<()> {
+    io::copy(
+        &mut reader.take((n as u64).saturating_sub(1)),
+        &mut io::sink(),
+    )?;
+    io::copy(reader, out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const TEXT: &str = "one\ntwo\nthree\nfour\n";
+
+    #[test]
+    fn シーク可能な入力とストリーム入力で末尾の行が一致する() {
+        for (n, expected) in [(1, "four\n"), (2, "three\nfour\n"), (10, TEXT)] {
+            let mut seeked = vec![];
+            last_lines_seek(&mut Cursor::new(TEXT), n, &mut seeked).unwrap();
+            let mut streamed = vec![];
+            last_lines(&mut Cursor::new(TEXT), n, &mut streamed).unwrap();
+
+            assert_eq!(String::from_utf8(seeked).unwrap(), expected);
+            assert_eq!(String::from_utf8(streamed).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn 最終行に改行がないとき_最終行も1行として数える() {
+        let mut actual = vec![];
+        last_lines_seek(&mut Cursor::new("a\nb\nc"), 2, &mut actual).unwrap();
+        assert_eq!(actual, b"b\nc");
+    }
+
+    #[test]
+    fn ブロックサイズを超える入力の末尾バイトを出力する() {
+        let text = "x".repeat(BLOCK_SIZE * 2) + "tail";
+        let mut seeked = vec![];
+        last_bytes_seek(&mut Cursor::new(&text), 4, &mut seeked).unwrap();
+        let mut streamed = vec![];
+        last_bytes(&mut Cursor::new(&text), 4, &mut streamed).unwrap();
+
+        assert_eq!(seeked, b"tail");
+        assert_eq!(streamed, b"tail");
+    }
+
+    #[test]
+    fn 先頭から数えた位置以降を出力する() {
+        let mut lines = vec![];
+        lines_from(&mut Cursor::new(TEXT), 3, &mut lines).unwrap();
+        let mut bytes = vec![];
+        bytes_from(&mut Cursor::new(TEXT), 5, &mut bytes).unwrap();
+
+        assert_eq!(lines, b"three\nfour\n");
+        assert_eq!(bytes, b"two\nthree\nfour\n");
+    }
+
+    #[test]
+    fn 数に0を指定したとき_末尾からは何も出力せず先頭からはすべて出力する() {
+        let mut seeked = vec![];
+        last_lines_seek(&mut Cursor::new(TEXT), 0, &mut seeked).unwrap();
+        let mut streamed = vec![];
+        last_lines(&mut Cursor::new(TEXT), 0, &mut streamed).unwrap();
+        let mut lines = vec![];
+        lines_from(&mut Cursor::new(TEXT), 0, &mut lines).unwrap();
+        let mut bytes = vec![];
+        bytes_from(&mut Cursor::new(TEXT), 0, &mut bytes).unwrap();
+
+        assert!(seeked.is_empty());
+        assert!(streamed.is_empty());
+        assert_eq!(lines, TEXT.as_bytes());
+        assert_eq!(bytes, TEXT.as_bytes());
+    }
+}
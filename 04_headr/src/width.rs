@@ -0,0 +1,190 @@
+use std::io::{self, Write};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const TAB_STOP: usize = 8;
+
+#[derive(Debug, Clone)]
+pub(crate) enum Overflow {
+    /// 超えた部分を切り捨てる。`ellipsis` があれば幅に含めて末尾に付ける
+    Truncate { ellipsis: Option<String> },
+    /// 超えた部分を次の行に折り返す
+    Wrap,
+}
+
+/// 書き込まれた各行を表示幅 `max_width` に収めて `out` に書き出すアダプタ
+///
+/// 幅は書記素クラスタ単位で数え、東アジアの全角文字は2桁、タブは8桁ごとのタブストップまでとする
+pub(crate) struct WidthLimiter<W: Write> {
+    out: W,
+    max_width: usize,
+    overflow: Overflow,
+    line: Vec<u8>,
+}
+
+fn grapheme_width(grapheme: &str, column: usize) -> usize {
+    if grapheme == "\t" {
+        TAB_STOP - column % TAB_STOP
+    } else {
+        grapheme.width()
+    }
+}
+
+fn display_width(text: &str) -> usize {
+    text.graphemes(true).fold(0, |column, grapheme| {
+        column + grapheme_width(grapheme, column)
+    })
+}
+
+impl<W: Write> WidthLimiter<W> {
+    pub(crate) fn new(out: W, max_width: usize, overflow: Overflow) -> Self {
+        WidthLimiter {
+            out,
+            max_width,
+            overflow,
+            line: Vec::new(),
+        }
+    }
+
+    /// 改行で終わっていない最後の行を書き出す
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.write_line()?;
+        self.out.flush()
+    }
+
+    fn write_line(&mut self) -> io::Result<()> {
+        if self.line.is_empty() {
+            return Ok(());
+        }
+        let line = std::mem::take(&mut self.line);
+        let content = line.strip_suffix(b"\n").unwrap_or(&line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        let terminator = &line[content.len()..];
+        let text = String::from_utf8_lossy(content);
+        // 収まる行は不正な UTF-8 も含めて元のバイト列のまま書き出す。置換した文字列は切るときだけ使う
+        if display_width(&text) <= self.max_width {
+            self.out.write_all(content)?;
+            return self.out.write_all(terminator);
+        }
+        match &self.overflow {
+            Overflow::Truncate { ellipsis } => {
+                truncate(&mut self.out, &text, self.max_width, ellipsis.as_deref())?
+            }
+            Overflow::Wrap => wrap(&mut self.out, &text, self.max_width)?,
+        }
+        self.out.write_all(terminator)
+    }
+}
+
+/// `max_width` に収まらない行を切り詰める
+fn truncate(
+    out: &mut impl Write,
+    text: &str,
+    max_width: usize,
+    ellipsis: Option<&str>,
+) -> io::Result<()> {
+    let ellipsis = ellipsis.unwrap_or("");
+    let budget = max_width.saturating_sub(ellipsis.width());
+    let mut column = 0;
+    for grapheme in text.graphemes(true) {
+        let width = grapheme_width(grapheme, column);
+        if column + width > budget {
+            break;
+        }
+        out.write_all(grapheme.as_bytes())?;
+        column += width;
+    }
+    out.write_all(ellipsis.as_bytes())
+}
+
+fn wrap(out: &mut impl Write, text: &str, max_width: usize) -> io::Result<()> {
+    let mut column = 0;
+    for grapheme in text.graphemes(true) {
+        let mut width = grapheme_width(grapheme, column);
+        if column + width > max_width && column > 0 {
+            out.write_all(b"\n")?;
+            column = 0;
+            width = grapheme_width(grapheme, column);
+        }
+        out.write_all(grapheme.as_bytes())?;
+        column += width;
+    }
+    Ok(())
+}
+
+impl<W: Write> Write for WidthLimiter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for chunk in buf.split_inclusive(|&b| b == b'\n') {
+            self.line.extend_from_slice(chunk);
+            if chunk.ends_with(b"\n") {
+                self.write_line()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(text: &str, max_width: usize, overflow: Overflow) -> String {
+        let mut out = vec![];
+        let mut limiter = WidthLimiter::new(&mut out, max_width, overflow);
+        limiter.write_all(text.as_bytes()).unwrap();
+        limiter.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn 全角文字を2桁として数えて切り詰める() {
+        let overflow = Overflow::Truncate { ellipsis: None };
+        assert_eq!(limit("日本語のテキスト\nabc\n", 5, overflow), "日本\nabc\n");
+    }
+
+    #[test]
+    fn 省略記号の幅を含めて切り詰める() {
+        let overflow = Overflow::Truncate {
+            ellipsis: Some("…".to_string()),
+        };
+        assert_eq!(limit("abcdefgh\r\nabcd", 4, overflow), "abc…\r\nabcd");
+    }
+
+    #[test]
+    fn 結合文字を分割しない() {
+        let overflow = Overflow::Truncate { ellipsis: None };
+        assert_eq!(
+            limit("e\u{301}e\u{301}e\u{301}\n", 2, overflow),
+            "e\u{301}e\u{301}\n"
+        );
+    }
+
+    #[test]
+    fn 折り返すとき_全角文字が境界をまたがないように次の行に送る() {
+        assert_eq!(limit("aあいう\n", 4, Overflow::Wrap), "aあ\nいう\n");
+    }
+
+    #[test]
+    fn 収まる行は不正なバイト列を含んでもそのまま書き出す() {
+        let mut out = vec![];
+        let mut limiter = WidthLimiter::new(&mut out, 4, Overflow::Wrap);
+        limiter.write_all(b"caf\xe9\nabcd\xe9\n").unwrap();
+        limiter.finish().unwrap();
+        // 折り返す行だけが置換文字に変わる
+        assert_eq!(
+            out,
+            [&b"caf\xe9\nabcd\n"[..], "\u{fffd}\n".as_bytes()].concat()
+        );
+    }
+
+    #[test]
+    fn タブはタブストップまでの幅として数える() {
+        let overflow = Overflow::Truncate { ellipsis: None };
+        assert_eq!(limit("a\tbcdefghij\n", 10, overflow), "a\tbc\n");
+    }
+}
@@ -211,3 +211,561 @@ fn 複数ファイルを指定されたとき_それぞれのファイルに対
     // Assert
     assert_eq_with_file(actual, "tests/expected/all.n2.out");
 }
+
+#[test]
+fn 末尾モードで行数オプションを指定したとき_ファイルの末尾の行が出力される() {
+    // Act
+    let actual = run_file(&["--from-end", "-n", "2", TWELVE]);
+    // Assert
+    assert_eq!(actual, "eleven\ntwelve\n");
+}
+
+#[test]
+fn 末尾モードで標準入力を指定したとき_末尾の行が出力される() {
+    // Act
+    let actual = run_stdin(&["--tail", "-n", "2"], "abc\r\ndef\nghi");
+    // Assert
+    assert_eq!(actual, "def\nghi");
+}
+
+#[test]
+fn 末尾モードでプラス付きの行数を指定したとき_指定された行以降が出力される() {
+    // Act
+    let actual = run_file(&["--from-end", "-n", "+11", TWELVE]);
+    // Assert
+    assert_eq!(actual, "eleven\ntwelve\n");
+}
+
+#[test]
+fn 末尾モードでバイト数オプションを指定したとき_ファイルの末尾のバイトが出力される() {
+    // Act
+    let from_end = run_file(&["--from-end", "-c", "7", TWELVE]);
+    let from_start = run_stdin(&["--from-end", "-c", "+5"], "abc\ndef\n");
+    // Assert
+    assert_eq!(from_end, "twelve\n");
+    assert_eq!(from_start, "def\n");
+}
+
+#[test]
+fn tailrで複数のファイルを指定されたとき_それぞれのファイルの末尾が見出し付きで出力される() {
+    // Act
+    let output = Command::cargo_bin("tailr")
+        .expect("failed to run command")
+        .args(["-n", "1", ONE, TWELVE])
+        .output()
+        .expect("fail");
+    // Assert
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("==> {ONE} <==\nÖne line, four words.\n\n==> {TWELVE} <==\ntwelve\n")
+    );
+}
+
+#[test]
+fn tailrで先頭から読むときだけの引数を指定したとき_エラーになる() {
+    for args in [
+        vec!["--number", "-n", "3"],
+        vec!["--records", "csv"],
+        vec!["--offset", "5"],
+    ] {
+        Command::cargo_bin("tailr")
+            .expect("failed to run command")
+            .args(&args)
+            .arg(TWELVE)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "the argument '{}' cannot be used with tailr",
+                args[0]
+            )));
+    }
+}
+
+#[test]
+fn 末尾モードで数に0を指定したとき_末尾からは何も出力せず先頭からはすべて出力される() {
+    // Act
+    let last = run_file(&["--from-end", "-n", "0", TWELVE]);
+    let from_start = run_file(&["--from-end", "-n", "+0", TWELVE]);
+    // Assert
+    assert_eq!(last, "");
+    assert_eq_with_file(from_start, TWELVE);
+    Command::cargo_bin(PRG)
+        .expect("failed to run command")
+        .args(["-n", "0", TWELVE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Value must be a positive integer"));
+}
+
+/// `--pid` に渡すプロセスが終了するまで追跡し、その間に `action` でファイルを更新する
+fn run_follow(args: &[&str], action: impl FnOnce()) -> (String, String) {
+    let mut sleeper = process::Command::new("sleep")
+        .arg("1")
+        .spawn()
+        .expect("failed to run sleep");
+    let child = process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(args)
+        .args(["-s", "0.1", "--pid", &sleeper.id().to_string()])
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .expect("failed to run command");
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    action();
+    sleeper.wait().expect("sleep failed");
+    let output = child.wait_with_output().expect("fail");
+    assert!(output.status.success());
+    (
+        String::from_utf8_lossy(&output.stdout).into(),
+        String::from_utf8_lossy(&output.stderr).into(),
+    )
+}
+
+fn append(path: &std::path::Path, text: &str) {
+    use std::io::Write;
+    let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(text.as_bytes()).unwrap();
+}
+
+#[test]
+fn 追跡モードのとき_追記されたデータが出力される() {
+    // Arrange
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "first\n").unwrap();
+
+    // Act
+    let (stdout, _) = run_follow(&["-f", "-n", "1", path.to_str().unwrap()], || {
+        append(&path, "second\n")
+    });
+
+    // Assert
+    assert_eq!(stdout, "first\nsecond\n");
+}
+
+#[test]
+fn 追跡モードで行数に0を指定したとき_追記されたデータだけが出力される() {
+    // Arrange
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "first\n").unwrap();
+
+    // Act
+    let (stdout, _) = run_follow(&["-f", "-n", "0", path.to_str().unwrap()], || {
+        append(&path, "second\n")
+    });
+
+    // Assert
+    assert_eq!(stdout, "second\n");
+}
+
+#[test]
+fn ポーリングで複数のファイルを追跡するとき_出力元が切り替わると見出しが出力される() {
+    // Arrange
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.txt");
+    let b = dir.path().join("b.txt");
+    fs::write(&a, "a1\n").unwrap();
+    fs::write(&b, "b1\n").unwrap();
+    let (a_name, b_name) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    // Act
+    let (stdout, _) = run_follow(&["-f", "--disable-inotify", a_name, b_name], || {
+        append(&a, "a2\n")
+    });
+
+    // Assert
+    assert_eq!(
+        stdout,
+        format!("==> {a_name} <==\na1\n\n==> {b_name} <==\nb1\n\n==> {a_name} <==\na2\n")
+    );
+}
+
+#[test]
+fn 追跡モードで最後のファイルを開けないとき_追記されたファイルの見出しが出力される() {
+    // Arrange
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.txt");
+    let missing = dir.path().join("missing.txt");
+    fs::write(&a, "a1\n").unwrap();
+    let (a_name, missing_name) = (a.to_str().unwrap(), missing.to_str().unwrap());
+
+    // Act
+    let (stdout, stderr) = run_follow(&["-f", "--disable-inotify", a_name, missing_name], || {
+        append(&a, "a2\n")
+    });
+
+    // Assert
+    assert_eq!(
+        stdout,
+        format!("==> {a_name} <==\na1\n\n==> {missing_name} <==\n\n==> {a_name} <==\na2\n")
+    );
+    assert!(stderr.contains(missing_name));
+}
+
+#[test]
+fn ファイル名で追跡するとき_ローテーションされたファイルを開き直す() {
+    // Arrange
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("log.txt");
+    fs::write(&path, "old\n").unwrap();
+
+    // Act
+    let (stdout, stderr) = run_follow(&["-F", path.to_str().unwrap()], || {
+        fs::rename(&path, dir.path().join("log.txt.1")).unwrap();
+        fs::write(&path, "new\n").unwrap();
+    });
+
+    // Assert
+    assert_eq!(stdout, "old\nnew\n");
+    assert!(stderr.contains("file has been replaced"));
+}
+
+#[test]
+fn レコードモードのとき_引用符で囲まれた改行を含むレコードを途中で切らない() {
+    // Act
+    let actual = run_stdin(
+        &["--records", "csv", "--keep-header", "-n", "1"],
+        "id,note\n1,\"first\nsecond\"\n2,x\n",
+    );
+    // Assert
+    assert_eq!(actual, "id,note\n1,\"first\nsecond\"\n");
+}
+
+#[test]
+fn サンプルモードで同じシードを指定したとき_同じ行が出力される() {
+    // Act
+    let first = run_file(&["--sample", "3", "--seed", "7", TWELVE]);
+    let second = run_file(&["--sample", "3", "--seed", "7", TWELVE]);
+    // Assert
+    assert_eq!(first, second);
+    assert_eq!(first.lines().count(), 3);
+}
+
+#[test]
+fn サンプルモードで行数を指定したとき_エラーになる() {
+    Command::cargo_bin(PRG)
+        .expect("failed to run command")
+        .args(["--sample", "2", "-n", "1", TWELVE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn 間隔を指定したとき_指定された間隔ごとの行が出力される() {
+    // Act
+    let actual = run_file(&["--every", "5", TWELVE]);
+    // Assert
+    assert_eq!(actual, "one\nsix\neleven\n");
+}
+
+#[test]
+fn 終了パターンを指定したとき_マッチする行の手前まで出力される() {
+    // Act
+    let exclusive = run_file(&["--until", "^fo", TWELVE]);
+    let inclusive = run_file(&["--until-inclusive", "^fo", TWELVE]);
+    // Assert
+    assert_eq!(exclusive, "one\ntwo\nthree\n");
+    assert_eq!(inclusive, "one\ntwo\nthree\nfour\n");
+}
+
+#[test]
+fn 開始パターンと行数を指定したとき_マッチした行の後から指定された行数が出力される() {
+    // Act
+    let actual = run_file(&["--after", "^ten$", "-n", "1", TWELVE]);
+    // Assert
+    assert_eq!(actual, "eleven\n");
+}
+
+#[test]
+fn 不正な正規表現を指定したとき_エラーになる() {
+    Command::cargo_bin(PRG)
+        .expect("Failed to run command")
+        .args(["--until", "(", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value '(' for '--until <REGEX>'",
+        ));
+}
+
+#[test]
+fn 単位付きのバイト数を指定したとき_必要な分だけ読み込んで出力する() {
+    // Act
+    let actual = run_file(&["-c", "50G", ONE]);
+    // Assert
+    assert_eq_with_file(actual, ONE);
+}
+
+#[test]
+fn オフセットを指定したとき_指定されたバイト位置から出力される() {
+    // Act
+    let from_file = run_file(&["--offset", "4", "-c", "4", TWELVE]);
+    let from_stdin = run_stdin(&["--offset", "4", "-n", "1"], "one\ntwo\nthree\n");
+    // Assert
+    assert_eq!(from_file, "two\n");
+    assert_eq!(from_stdin, "two\n");
+}
+
+#[test]
+fn json出力を指定したとき_ファイルごとにオブジェクトが出力される() {
+    // Arrange
+    let bad = gen_bad_file();
+
+    // Act
+    let actual = run_file(&["--json", "-n", "2", TWO, &bad]);
+
+    // Assert
+    let objects: Vec<serde_json::Value> = actual
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        objects[0],
+        serde_json::json!({
+            "file": TWO,
+            "lines": ["Two lines.", "Four words."],
+            "truncated": false,
+        })
+    );
+    assert_eq!(objects[1]["file"], bad.as_str());
+    assert!(objects[1]["error"]
+        .as_str()
+        .unwrap()
+        .contains("(os error 2)"));
+}
+
+#[test]
+fn json出力で行数の続きがあるとき_truncatedがtrueになる() {
+    // Act
+    let actual = run_file(&["--json", "-n", "1", TWELVE]);
+    // Assert
+    assert_eq!(
+        actual,
+        format!("{{\"file\":\"{TWELVE}\",\"lines\":[\"one\"],\"truncated\":true}}\n")
+    );
+}
+
+#[test]
+fn jsonlモードのとき_不正なレコードが行番号付きで報告される() {
+    // Act
+    let output = Command::cargo_bin(PRG)
+        .expect("failed to run command")
+        .args(["--jsonl", "--fields", "id,user.name", "-"])
+        .write_stdin("{\"id\":1,\"user\":{\"name\":\"a\",\"age\":3}}\n{oops\n{\"id\":2}\n")
+        .output()
+        .expect("fail");
+
+    // Assert
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{\"id\":1,\"user\":{\"name\":\"a\"}}\n{\"id\":2}\n"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("-:2: invalid JSON: "));
+}
+
+#[test]
+fn jsonlモードで整形を指定したとき_レコードが整形されて出力される() {
+    // Act
+    let actual = run_stdin(&["--jsonl", "--pretty"], "{\"b\":1,\"a\":[2]}\n");
+    // Assert
+    assert_eq!(actual, "{\n  \"b\": 1,\n  \"a\": [\n    2\n  ]\n}\n");
+}
+
+#[test]
+fn 並列数を指定したとき_引数の順にセパレータで区切られて出力される() {
+    // Act
+    let actual = run_file(&["--jobs", "3", "-n", "2", EMPTY, ONE, TWO, THREE, TWELVE]);
+    // Assert
+    assert_eq_with_file(actual, "tests/expected/all.n2.out");
+}
+
+#[test]
+fn 圧縮されたファイルを指定したとき_伸長した内容の先頭が出力される() {
+    use std::io::Write;
+
+    // Arrange
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("twelve.txt.gz");
+    let mut encoder =
+        flate2::write::GzEncoder::new(File::create(&path).unwrap(), flate2::Compression::default());
+    encoder.write_all(&fs::read(TWELVE).unwrap()).unwrap();
+    encoder.finish().unwrap();
+    let gz = path.to_str().unwrap();
+
+    // Act
+    let actual = run_file(&["-n", "2", ONE, gz]);
+
+    // Assert
+    assert_eq!(
+        actual,
+        format!("==> {ONE} <==\nÖne line, four words.\n\n==> {gz} <==\none\ntwo\n")
+    );
+}
+
+#[test]
+fn 圧縮形式のマジックバイトで始まるテキストを指定したとき_そのまま出力される() {
+    // Arrange
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("t.txt");
+    fs::write(&path, "BZh9 is my favourite\n").unwrap();
+
+    // Act
+    let from_file = run_file(&[path.to_str().unwrap()]);
+    let from_stdin = run_stdin(&[], "BZh9 is my favourite\n");
+
+    // Assert
+    assert_eq!(from_file, "BZh9 is my favourite\n");
+    assert_eq!(from_stdin, "BZh9 is my favourite\n");
+}
+
+#[test]
+fn 最大幅を指定したとき_各行が表示幅で切り詰められる() {
+    // Act
+    let truncated = run_stdin(&["--max-width", "6", "--ellipsis"], "こんにちは\nabc\n");
+    let wrapped = run_stdin(&["--max-width", "6", "--wrap"], "こんにちは\n");
+    // Assert
+    assert_eq!(truncated, "こん…\nabc\n");
+    assert_eq!(wrapped, "こんに\nちは\n");
+}
+
+#[test]
+fn 連結モードで出力をためるモードと見出しを指定したとき_エラーになる() {
+    for (prg, args) in [
+        (PRG, vec!["--from-end", "-n", "3"]),
+        (PRG, vec!["--sample", "2"]),
+        (PRG, vec!["--jsonl"]),
+        ("tailr", vec!["-n", "3"]),
+    ] {
+        Command::cargo_bin(prg)
+            .expect("failed to run command")
+            .args(["--concat", "-v"])
+            .args(args)
+            .args([ONE, TWO])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "the argument '--verbose' cannot be used with '--concat'",
+            ));
+    }
+}
+
+#[test]
+fn 連結モードのとき_すべてのファイルをつなげた先頭の行が出力される() {
+    // Act
+    let actual = run_file(&["--concat", "-n", "4", EMPTY, ONE, TWO, TWELVE]);
+    // Assert
+    assert_eq!(
+        actual,
+        "Öne line, four words.\nTwo lines.\nFour words.\none\n"
+    );
+}
+
+#[test]
+fn 連結モードで見出しを指定したとき_出力に含まれるファイルの見出しだけが出力される() {
+    // Act
+    let actual = run_file(&["--concat", "-v", "-n", "2", EMPTY, ONE, TWO, TWELVE]);
+    // Assert
+    assert_eq!(
+        actual,
+        format!("==> {ONE} <==\nÖne line, four words.\n\n==> {TWO} <==\nTwo lines.\n")
+    );
+}
+
+#[test]
+fn 見出しを抑制したとき_複数のファイルが見出しなしで出力される() {
+    // Act
+    let quiet = run_file(&["-q", "-n", "1", ONE, TWELVE]);
+    let verbose = run_file(&["-v", "-n", "1", ONE]);
+    // Assert
+    assert_eq!(quiet, "Öne line, four words.\none\n");
+    assert_eq!(verbose, format!("==> {ONE} <==\nÖne line, four words.\n"));
+}
+
+#[test]
+fn ファイルを指定しないとき_標準入力から読み込む() {
+    // Act
+    let output = Command::cargo_bin(PRG)
+        .expect("failed to run command")
+        .args(["-n", "1"])
+        .write_stdin("abc\ndef\n")
+        .output()
+        .expect("fail");
+    // Assert
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "abc\n");
+}
+
+#[test]
+fn 補完スクリプトの生成を指定したとき_指定されたシェルの補完スクリプトが出力される() {
+    for (shell, expected) in [
+        ("bash", "_headr()"),
+        ("zsh", "#compdef headr"),
+        ("fish", "complete -c headr"),
+    ] {
+        let actual = run_file(&["--generate-completions", shell]);
+        assert!(actual.contains(expected), "{shell}: {actual}");
+    }
+}
+
+#[test]
+fn manページの生成を指定したとき_引数の定義からmanページが出力される() {
+    // Act
+    let actual = run_file(&["--generate-man"]);
+    // Assert
+    assert!(actual.contains(".TH headr"));
+    assert!(actual.contains("\\-\\-lines"));
+}
+
+#[test]
+fn ヘルプを表示したとき_主な引数に説明が付きtailrでは先頭から読むときだけの引数が出ない() {
+    // Act
+    let head = run_file(&["--help"]);
+    let tail = Command::cargo_bin("tailr")
+        .expect("failed to run command")
+        .arg("--help")
+        .output()
+        .expect("fail");
+    let tail = String::from_utf8_lossy(&tail.stdout);
+    // Assert
+    assert!(head.contains("Files to read"));
+    assert!(head.contains("Print the first N lines"));
+    assert!(head.contains("--number"));
+    assert!(tail.contains("Print the last N lines"));
+    for head_only in ["--from-end", "--number", "--records", "--offset", "--jsonl"] {
+        assert!(!tail.contains(head_only), "{head_only}: {tail}");
+    }
+}
+
+#[test]
+fn 行番号を指定したとき_各行の先頭に行番号が付く() {
+    // Act
+    let actual = run_stdin(&["--number", "-n", "2"], "one\ntwo\nthree\n");
+    // Assert
+    assert_eq!(actual, "1:one\n2:two\n");
+}
+
+#[test]
+fn 行番号やバイトオフセットとjsonlを指定したとき_エラーになる() {
+    for annotation in ["--number", "--byte-offset"] {
+        Command::cargo_bin(PRG)
+            .expect("failed to run command")
+            .args([annotation, "--jsonl", ONE])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+}
+
+#[test]
+fn バイトオフセットを指定したとき_各行の先頭にファイル上の開始位置が付く() {
+    // Act
+    let actual = run_file(&["--byte-offset", "--offset", "4", "-n", "2", TWELVE]);
+    let both = run_stdin(&["--number", "--byte-offset", "-n", "2"], "one\ntwo\n");
+    // Assert
+    assert_eq!(actual, "4:two\n8:three\n");
+    assert_eq!(both, "1:0:one\n2:4:two\n");
+}
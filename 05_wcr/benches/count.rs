@@ -0,0 +1,105 @@
+//! Throughput of `wcr::count` against the previous line-at-a-time implementation.
+//!
+//! By default the input is 64 MiB of generated log-like text held in memory. Set
+//! `WCR_BENCH_FILE` to the path of a large file (several GB) to measure on real data,
+//! read from disk (or the page cache) on every iteration:
+//!
+//! ```sh
+//! WCR_BENCH_FILE=/var/log/big.log cargo bench --bench count
+//! ```
+
+use std::{
+    env,
+    error::Error,
+    fs::{self, File},
+    io::{BufRead, BufReader, Cursor, Read},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use wcr::{count, Encoding};
+
+const GENERATED_SIZE: usize = 64 * 1024 * 1024;
+
+/// The counting loop before the chunked engine: one `String` per line,
+/// `split_whitespace` for words and `chars().count()` for characters.
+fn count_by_line(mut handle: impl BufRead) -> Result<(usize, usize, usize, usize), Box<dyn Error>> {
+    let mut num_lines = 0;
+    let mut num_words = 0;
+    let mut num_bytes = 0;
+    let mut num_chars = 0;
+
+    let mut line = String::new();
+    loop {
+        let read = handle.read_line(&mut line)?;
+        if read == 0 {
+            break;
+        }
+        num_lines += 1;
+        num_bytes += line.len();
+        num_chars += line.chars().count();
+        num_words += line.split_whitespace().count();
+
+        line.clear();
+    }
+    Ok((num_lines, num_words, num_bytes, num_chars))
+}
+
+fn generated_log() -> Vec<u8> {
+    let lines = [
+        "2024-05-01T12:00:00Z INFO  request handled path=/api/v1/items status=200 elapsed=3ms\n",
+        "2024-05-01T12:00:01Z WARN  slow query table=orders rows=10482 elapsed=1520ms\n",
+        "2024-05-01T12:00:02Z ERROR upstream timed out host=10.0.0.12:8080 retries=3\n",
+        "2024-05-01T12:00:03Z INFO  ユーザーがログインしました user=taro\n",
+    ];
+    let mut text = Vec::with_capacity(GENERATED_SIZE);
+    while text.len() < GENERATED_SIZE {
+        for line in lines {
+            text.extend_from_slice(line.as_bytes());
+        }
+    }
+    text
+}
+
+fn bench_count(c: &mut Criterion) {
+    let path = env::var("WCR_BENCH_FILE")
+        .ok()
+        .filter(|path| !path.is_empty());
+    let generated = match path {
+        Some(_) => vec![],
+        None => generated_log(),
+    };
+    // A file given in WCR_BENCH_FILE is streamed on every iteration rather than held in memory
+    let open = || -> Box<dyn Read + '_> {
+        match &path {
+            Some(path) => Box::new(File::open(path).expect("failed to open WCR_BENCH_FILE")),
+            None => Box::new(Cursor::new(black_box(&generated))),
+        }
+    };
+    let size = match &path {
+        Some(path) => fs::metadata(path)
+            .expect("failed to stat WCR_BENCH_FILE")
+            .len(),
+        None => generated.len() as u64,
+    };
+
+    let name = path.as_deref().unwrap_or("generated log");
+    let mut group = c.benchmark_group(format!("count {}", name));
+    group.throughput(Throughput::Bytes(size));
+    group.sample_size(10);
+    group.bench_function("chunked", |b| {
+        b.iter(|| count(open(), Encoding::Utf8, false).unwrap())
+    });
+    group.bench_function("chunked C locale", |b| {
+        b.iter(|| count(open(), Encoding::SingleByte, false).unwrap())
+    });
+    group.bench_function("chunked with -L", |b| {
+        b.iter(|| count(open(), Encoding::Utf8, true).unwrap())
+    });
+    group.bench_function("by line", |b| {
+        b.iter(|| count_by_line(BufReader::new(open())).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_count);
+criterion_main!(benches);
@@ -0,0 +1,483 @@
+use std::env;
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::FileInfo;
+
+const TAB_STOP: usize = 8;
+
+/// Runs of one-byte characters are scanned in blocks of this size, so that a
+/// control character only sends its own block down the stateful word loop.
+const BLOCK_SIZE: usize = 4096;
+
+/// A printable ASCII character, which starts or continues a word
+fn is_word_byte(byte: u8) -> bool {
+    byte.wrapping_sub(b'!') <= b'~' - b'!'
+}
+
+/// ASCII whitespace, which ends a word
+fn is_space_byte(byte: u8) -> bool {
+    byte == b' ' || (b'\t'..=b'\r').contains(&byte)
+}
+
+/// Length of the leading run of ASCII bytes, checked a word at a time
+fn ascii_prefix_len(bytes: &[u8]) -> usize {
+    let mut len = 0;
+    for block in bytes.chunks(64) {
+        if !block.is_ascii() {
+            return len + block.iter().take_while(|byte| byte.is_ascii()).count();
+        }
+        len += block.len();
+    }
+    len
+}
+
+/// How input bytes are decoded into characters, following the `LC_CTYPE` locale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    /// Multibyte UTF-8. Invalid sequences count as bytes but not as characters.
+    Utf8,
+    /// One byte per character, as in the `C`/`POSIX` locale.
+    SingleByte,
+}
+
+impl Encoding {
+    /// Reads the locale from `LC_ALL`, `LC_CTYPE` and `LANG` in that order.
+    /// An unset locale is the `C` locale, as for GNU wc.
+    pub fn from_env() -> Self {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty());
+        match locale {
+            None => Encoding::SingleByte,
+            Some(locale) => Encoding::from_locale(&locale),
+        }
+    }
+
+    fn from_locale(locale: &str) -> Self {
+        let codeset = locale
+            .split_once('.')
+            .map(|(_, codeset)| codeset.split('@').next().unwrap_or(codeset))
+            .unwrap_or("");
+        let is_utf8 = codeset.eq_ignore_ascii_case("utf-8") || codeset.eq_ignore_ascii_case("utf8");
+        if is_utf8 || (codeset.is_empty() && locale != "C" && locale != "POSIX") {
+            Encoding::Utf8
+        } else {
+            Encoding::SingleByte
+        }
+    }
+}
+
+/// Counts lines, words, bytes and characters over input fed in arbitrary chunks.
+///
+/// Words are counted the way GNU wc does: whitespace separates words, a printable
+/// character starts one, and control characters and invalid bytes do neither.
+#[derive(Debug)]
+pub struct Counter {
+    encoding: Encoding,
+    /// Whether to measure line widths, which needs every byte to be decoded
+    max_line_length: bool,
+    /// Whether no-break spaces separate words, as in GNU wc unless `POSIXLY_CORRECT` is set
+    nbsp_separates: bool,
+    info: FileInfo,
+    in_word: bool,
+    /// Whether the first character that starts or ends a word started one, so that
+    /// a word cut by a range boundary can be counted once when ranges are joined
+    starts_in_word: Option<bool>,
+    /// Display width of the current line so far
+    line_width: usize,
+    /// Code point decoded so far from an incomplete UTF-8 sequence
+    code_point: u32,
+    /// Continuation bytes still needed to finish the current sequence
+    needed: u8,
+    /// Valid range of the next continuation byte, which rules out overlong
+    /// forms, surrogates and values above U+10FFFF
+    lower: u8,
+    upper: u8,
+}
+
+impl Counter {
+    pub fn new(encoding: Encoding, max_line_length: bool) -> Self {
+        Counter {
+            encoding,
+            max_line_length,
+            nbsp_separates: env::var_os("POSIXLY_CORRECT").is_none(),
+            info: FileInfo::default(),
+            in_word: false,
+            starts_in_word: None,
+            line_width: 0,
+            code_point: 0,
+            needed: 0,
+            lower: 0x80,
+            upper: 0xbf,
+        }
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.info.num_bytes += chunk.len();
+        if self.max_line_length {
+            self.update_bytewise(chunk);
+            return;
+        }
+        match self.encoding {
+            Encoding::SingleByte => {
+                for block in chunk.chunks(BLOCK_SIZE) {
+                    self.update_one_byte_chars(block);
+                }
+            }
+            Encoding::Utf8 => {
+                let mut rest = chunk;
+                while !rest.is_empty() {
+                    // Decode multibyte sequences until the next ASCII byte that starts a character
+                    let mut decoded = 0;
+                    while decoded < rest.len() && (self.needed > 0 || !rest[decoded].is_ascii()) {
+                        self.utf8_byte(rest[decoded]);
+                        decoded += 1;
+                    }
+                    rest = &rest[decoded..];
+                    let ascii = ascii_prefix_len(rest);
+                    for block in rest[..ascii].chunks(BLOCK_SIZE) {
+                        self.update_one_byte_chars(block);
+                    }
+                    rest = &rest[ascii..];
+                }
+            }
+        }
+    }
+
+    /// Counts a block where every byte is a character without decoding it.
+    ///
+    /// Newlines are found with memchr. When the block has only printable
+    /// characters and whitespace, word starts are the printable bytes that follow
+    /// a non-printable one, which is a loop without carried state that the compiler
+    /// can vectorize. Blocks with control characters, which neither start nor end
+    /// a word, fall back to tracking the word state byte by byte.
+    fn update_one_byte_chars(&mut self, block: &[u8]) {
+        let Some((&last, _)) = block.split_last() else {
+            return;
+        };
+        self.info.num_chars += block.len();
+        self.info.num_lines += memchr::memchr_iter(b'\n', block).count();
+        let has_control = block.chunks(u8::MAX.into()).any(|bytes| {
+            let controls = bytes.iter().fold(0u8, |controls, &byte| {
+                controls + u8::from(!(is_word_byte(byte) | is_space_byte(byte)))
+            });
+            controls > 0
+        });
+        if has_control {
+            for &byte in block {
+                if is_space_byte(byte) {
+                    self.end_word();
+                } else if is_word_byte(byte) {
+                    self.start_word();
+                }
+            }
+            return;
+        }
+        self.starts_in_word.get_or_insert(is_word_byte(block[0]));
+        let first = usize::from(is_word_byte(block[0]) && !self.in_word);
+        // Sums are kept in u8 over at most 255 bytes so that they fill whole vector registers
+        let starts: usize = block[1..]
+            .chunks(u8::MAX.into())
+            .zip(block.chunks(u8::MAX.into()))
+            .map(|(bytes, previous)| {
+                let starts = bytes
+                    .iter()
+                    .zip(previous)
+                    .fold(0u8, |starts, (&byte, &previous)| {
+                        starts + u8::from(is_word_byte(byte) & !is_word_byte(previous))
+                    });
+                usize::from(starts)
+            })
+            .sum();
+        self.info.num_words += first + starts;
+        self.in_word = is_word_byte(last);
+    }
+
+    fn update_bytewise(&mut self, block: &[u8]) {
+        match self.encoding {
+            Encoding::SingleByte => {
+                for &byte in block {
+                    self.info.num_chars += 1;
+                    self.byte(byte);
+                }
+            }
+            Encoding::Utf8 => {
+                for &byte in block {
+                    self.utf8_byte(byte);
+                }
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> FileInfo {
+        self.end_line();
+        self.info
+    }
+
+    /// Finishes counting one range of a larger input, keeping what [`Part::join`]
+    /// needs to know about words at its edges.
+    pub(crate) fn finish_part(self) -> Part {
+        let starts_in_word = self.starts_in_word;
+        let ends_in_word = self.in_word;
+        Part {
+            info: self.finish(),
+            starts_in_word,
+            ends_in_word,
+        }
+    }
+
+    fn byte(&mut self, byte: u8) {
+        match byte {
+            b'\n' => {
+                self.info.num_lines += 1;
+                self.end_line();
+            }
+            b'\r' | b'\x0c' => self.end_line(),
+            b'\t' => self.line_width += TAB_STOP - self.line_width % TAB_STOP,
+            b' ' => self.line_width += 1,
+            _ => {}
+        }
+        if is_space_byte(byte) {
+            self.end_word();
+        } else if is_word_byte(byte) {
+            self.line_width += 1;
+            self.start_word();
+        }
+    }
+
+    fn end_line(&mut self) {
+        if !self.max_line_length {
+            return;
+        }
+        self.info.num_max_line_length = self.info.num_max_line_length.max(self.line_width);
+        self.line_width = 0;
+    }
+
+    fn utf8_byte(&mut self, byte: u8) {
+        if self.needed > 0 {
+            if (self.lower..=self.upper).contains(&byte) {
+                self.code_point = self.code_point << 6 | u32::from(byte & 0x3f);
+                self.needed -= 1;
+                self.lower = 0x80;
+                self.upper = 0xbf;
+                if self.needed == 0 {
+                    let c = char::from_u32(self.code_point).expect("decoded a valid scalar value");
+                    self.char(c);
+                }
+                return;
+            }
+            // The sequence so far is invalid; this byte may start the next one
+            self.needed = 0;
+            self.lower = 0x80;
+            self.upper = 0xbf;
+        }
+        let (needed, bits) = match byte {
+            0x00..=0x7f => {
+                self.info.num_chars += 1;
+                self.byte(byte);
+                return;
+            }
+            0xc2..=0xdf => (1, byte & 0x1f),
+            0xe0..=0xef => (2, byte & 0x0f),
+            0xf0..=0xf4 => (3, byte & 0x07),
+            _ => return,
+        };
+        match byte {
+            0xe0 => self.lower = 0xa0,
+            0xed => self.upper = 0x9f,
+            0xf0 => self.lower = 0x90,
+            0xf4 => self.upper = 0x8f,
+            _ => {}
+        }
+        self.needed = needed;
+        self.code_point = u32::from(bits);
+    }
+
+    fn char(&mut self, c: char) {
+        self.info.num_chars += 1;
+        if c.is_control() {
+            return;
+        }
+        if self.max_line_length {
+            self.line_width += c.width().unwrap_or(0);
+        }
+        let is_nbsp = matches!(c, '\u{a0}' | '\u{2007}' | '\u{202f}' | '\u{2060}');
+        // glibc does not count no-break spaces as whitespace, so only GNU wc's own rule separates them
+        let separates = if is_nbsp {
+            self.nbsp_separates
+        } else {
+            c.is_whitespace()
+        };
+        if separates {
+            self.end_word();
+        } else {
+            self.start_word();
+        }
+    }
+
+    fn end_word(&mut self) {
+        self.starts_in_word.get_or_insert(false);
+        self.in_word = false;
+    }
+
+    fn start_word(&mut self) {
+        self.starts_in_word.get_or_insert(true);
+        if !self.in_word {
+            self.info.num_words += 1;
+            self.in_word = true;
+        }
+    }
+}
+
+/// Counts of one range of an input that was split to be counted in parallel
+#[derive(Debug)]
+pub(crate) struct Part {
+    pub(crate) info: FileInfo,
+    /// `None` when the range has no character that starts or ends a word
+    starts_in_word: Option<bool>,
+    ends_in_word: bool,
+}
+
+impl Part {
+    /// Combines the counts of this range with those of the range right after it.
+    /// A word running across the boundary was counted as starting in both ranges.
+    pub(crate) fn join(self, next: Part) -> Part {
+        let split_word = self.ends_in_word && next.starts_in_word == Some(true);
+        let mut info = self.info + next.info;
+        info.num_words -= usize::from(split_word);
+        let ends_in_word = match next.starts_in_word {
+            Some(_) => next.ends_in_word,
+            None => self.ends_in_word,
+        };
+        Part {
+            info,
+            starts_in_word: self.starts_in_word.or(next.starts_in_word),
+            ends_in_word,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_chunks(chunks: &[&[u8]], encoding: Encoding) -> FileInfo {
+        let mut counter = Counter::new(encoding, true);
+        for chunk in chunks {
+            counter.update(chunk);
+        }
+        counter.finish()
+    }
+
+    /// Lines, words, bytes and characters
+    fn counts(chunks: &[&[u8]], encoding: Encoding) -> (usize, usize, usize, usize) {
+        let info = count_chunks(chunks, encoding);
+        (
+            info.num_lines,
+            info.num_words,
+            info.num_bytes,
+            info.num_chars,
+        )
+    }
+
+    fn max_line_length(text: &[u8], encoding: Encoding) -> usize {
+        count_chunks(&[text], encoding).num_max_line_length
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_counted_as_bytes_only() {
+        assert_eq!(counts(&[b"a\xffb \xff\n"], Encoding::Utf8), (1, 1, 6, 4));
+        assert_eq!(counts(&[b"x\xe3\x81 y\n"], Encoding::Utf8), (1, 2, 6, 4));
+        assert_eq!(
+            counts(&[b"\xed\xa0\x80\xc0\xaf\xf4\x90\x80\x80"], Encoding::Utf8),
+            (0, 0, 9, 0)
+        );
+    }
+
+    #[test]
+    fn test_sequence_split_across_chunks() {
+        let text = "日本語 テキスト\n".as_bytes();
+        let chunks: Vec<&[u8]> = text.chunks(1).collect();
+        assert_eq!(counts(&chunks, Encoding::Utf8), (1, 2, 23, 9));
+    }
+
+    #[test]
+    fn test_control_characters_do_not_start_words() {
+        assert_eq!(counts(&[b"\x01 a\n"], Encoding::Utf8), (1, 1, 4, 4));
+        assert_eq!(
+            counts(&["é\n".as_bytes()], Encoding::SingleByte),
+            (1, 0, 3, 3)
+        );
+    }
+
+    #[test]
+    fn test_no_break_spaces_separate_words_unless_posixly_correct() {
+        let text = "a\u{a0}b c\u{2060}d\u{202f}e\u{2007}f\n".as_bytes();
+        assert_eq!(counts(&[text], Encoding::Utf8).1, 6);
+
+        let mut counter = Counter::new(Encoding::Utf8, false);
+        counter.nbsp_separates = false;
+        counter.update(text);
+        assert_eq!(counter.finish().num_words, 2);
+    }
+
+    #[test]
+    fn test_one_byte_chars_path_matches_bytewise_path() {
+        let with_controls =
+            "word  \x01x\tlog line\n".repeat(40) + "末尾\n" + &"ab \x0bcd\r\n".repeat(60);
+        let printable = " lead\tx  ".repeat(1000);
+        for (text, encoding) in [
+            (&with_controls, Encoding::Utf8),
+            (&with_controls, Encoding::SingleByte),
+            (&printable, Encoding::SingleByte),
+        ] {
+            for chunk_size in [1, 7, BLOCK_SIZE, text.len()] {
+                let mut counter = Counter::new(encoding, false);
+                for chunk in text.as_bytes().chunks(chunk_size) {
+                    counter.update(chunk);
+                }
+                let info = counter.finish();
+                let expected = count_chunks(&[text.as_bytes()], encoding);
+                assert_eq!(
+                    (
+                        info.num_lines,
+                        info.num_words,
+                        info.num_bytes,
+                        info.num_chars
+                    ),
+                    (
+                        expected.num_lines,
+                        expected.num_words,
+                        expected.num_bytes,
+                        expected.num_chars
+                    ),
+                    "{:?} in chunks of {}",
+                    encoding,
+                    chunk_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_line_length_uses_display_width() {
+        assert_eq!(max_line_length(b"ab\tc\nabc", Encoding::Utf8), 9);
+        assert_eq!(
+            max_line_length("日本語\ne\u{301}\n".as_bytes(), Encoding::Utf8),
+            6
+        );
+        assert_eq!(max_line_length(b"abcd\rab\x01\n", Encoding::Utf8), 4);
+        assert_eq!(max_line_length("日本".as_bytes(), Encoding::SingleByte), 0);
+    }
+
+    #[test]
+    fn test_encoding_from_locale() {
+        assert_eq!(Encoding::from_locale("en_US.UTF-8"), Encoding::Utf8);
+        assert_eq!(Encoding::from_locale("C.utf8"), Encoding::Utf8);
+        assert_eq!(Encoding::from_locale("C"), Encoding::SingleByte);
+        assert_eq!(Encoding::from_locale("POSIX"), Encoding::SingleByte);
+        assert_eq!(Encoding::from_locale("ja_JP.eucJP"), Encoding::SingleByte);
+    }
+}
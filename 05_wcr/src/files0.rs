@@ -0,0 +1,56 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Read},
+};
+
+use crate::layout;
+
+/// Lists in a regular file up to this size have their files stat'ed up front to
+/// size the columns, as GNU wc does. Other lists use the narrowest columns.
+const MAX_STATED_LIST_SIZE: u64 = 10 * 1024 * 1024;
+
+/// File names read from a `--files0-from` list
+pub(crate) struct FileList {
+    /// One entry per name in list order, or why the name cannot be used
+    pub(crate) entries: Vec<Result<String, &'static str>>,
+    /// Whether the listed files can be stat'ed to compute the column width
+    pub(crate) stat_files: bool,
+}
+
+/// Reads NUL-terminated file names from `source`, or from standard input for `-`.
+/// The last name may be left unterminated.
+pub(crate) fn read(source: &str) -> Result<FileList, Box<dyn Error>> {
+    let mut data = vec![];
+    let metadata = if source == "-" {
+        io::stdin().lock().read_to_end(&mut data)?;
+        layout::stdin_metadata()
+    } else {
+        let mut file = File::open(source)
+            .map_err(|err| format!("cannot open '{}' for reading: {}", source, err))?;
+        file.read_to_end(&mut data)?;
+        file.metadata()
+    };
+    let stat_files = metadata
+        .map(|metadata| metadata.is_file() && metadata.len() <= MAX_STATED_LIST_SIZE)
+        .unwrap_or(false);
+
+    let mut names: Vec<&[u8]> = data.split(|&byte| byte == 0).collect();
+    if data.is_empty() || data.ends_with(&[0]) {
+        names.pop();
+    }
+    let entries = names
+        .into_iter()
+        .map(|name| match name {
+            b"" => Err("invalid zero-length file name"),
+            b"-" if source == "-" => {
+                Err("when reading file names from standard input, no file name of '-' allowed")
+            }
+            _ => String::from_utf8(name.to_vec()).map_err(|_| "Invalid Unicode in file path"),
+        })
+        .collect();
+    Ok(FileList {
+        entries,
+        stat_files,
+    })
+}
@@ -0,0 +1,159 @@
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use clap::ValueEnum;
+
+use crate::FileInfo;
+
+/// How `--group-by` puts input files together
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    /// Files with the same extension
+    Ext,
+    /// Files in the same directory
+    Dir,
+    /// Files under the same directory at most N levels deep
+    Depth(usize),
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "ext" => Ok(GroupBy::Ext),
+            "dir" => Ok(GroupBy::Dir),
+            _ => match value.strip_prefix("depth=").map(str::parse) {
+                Some(Ok(depth)) if depth > 0 => Ok(GroupBy::Depth(depth)),
+                _ => Err("expected ext, dir or depth=N with N of at least 1".to_string()),
+            },
+        }
+    }
+}
+
+impl GroupBy {
+    /// The name of the group `filepath` belongs to. Standard input is a group of its own.
+    fn key(self, filepath: &str) -> String {
+        if filepath == "-" {
+            return filepath.to_string();
+        }
+        let path = Path::new(filepath);
+        let depth = match self {
+            GroupBy::Ext => {
+                return match path.extension() {
+                    Some(ext) => format!(".{}", ext.to_string_lossy()),
+                    None => "(none)".to_string(),
+                };
+            }
+            GroupBy::Dir => usize::MAX,
+            GroupBy::Depth(depth) => depth,
+        };
+
+        // "./a/b" and "a/b" are the same directory
+        let mut dir = PathBuf::new();
+        let mut level = 0;
+        for component in path.parent().into_iter().flat_map(Path::components) {
+            match component {
+                Component::CurDir => continue,
+                Component::Prefix(_) | Component::RootDir => {}
+                _ if level == depth => break,
+                _ => level += 1,
+            }
+            dir.push(component);
+        }
+        if dir.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            dir.to_string_lossy().into_owned()
+        }
+    }
+}
+
+/// The count that `--sort` orders groups by
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SortKey {
+    Lines,
+    Words,
+    Chars,
+    Bytes,
+}
+
+impl SortKey {
+    fn value(self, info: &FileInfo) -> usize {
+        match self {
+            SortKey::Lines => info.num_lines,
+            SortKey::Words => info.num_words,
+            SortKey::Chars => info.num_chars,
+            SortKey::Bytes => info.num_bytes,
+        }
+    }
+}
+
+/// Subtotals of the files in each group
+pub(crate) struct Groups {
+    by: GroupBy,
+    subtotals: BTreeMap<String, FileInfo>,
+}
+
+impl Groups {
+    pub(crate) fn new(by: GroupBy) -> Self {
+        Groups {
+            by,
+            subtotals: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, filepath: &str, info: FileInfo) {
+        *self.subtotals.entry(self.by.key(filepath)).or_default() += info;
+    }
+
+    /// The groups by name, or largest first by `sort` with ties by name
+    pub(crate) fn sorted(self, sort: Option<SortKey>) -> Vec<(String, FileInfo)> {
+        let mut groups: Vec<_> = self.subtotals.into_iter().collect();
+        if let Some(sort) = sort {
+            // Stable, so groups with the same count stay in name order
+            groups.sort_by_key(|(_, info)| std::cmp::Reverse(sort.value(info)));
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_group_by() {
+        assert_eq!("ext".parse(), Ok(GroupBy::Ext));
+        assert_eq!("dir".parse(), Ok(GroupBy::Dir));
+        assert_eq!("depth=2".parse(), Ok(GroupBy::Depth(2)));
+        for invalid in ["depth=0", "depth=", "depth=x", "depth", "name"] {
+            assert!(invalid.parse::<GroupBy>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_group_keys() {
+        let cases = [
+            (GroupBy::Ext, "src/lib.rs", ".rs"),
+            (GroupBy::Ext, "a.tar.gz", ".gz"),
+            (GroupBy::Ext, "Makefile", "(none)"),
+            (GroupBy::Ext, "-", "-"),
+            (GroupBy::Dir, "src/lib.rs", "src"),
+            (GroupBy::Dir, "./src/bin/main.rs", "src/bin"),
+            (GroupBy::Dir, "lib.rs", "."),
+            (GroupBy::Dir, "/usr/lib/x.so", "/usr/lib"),
+            (GroupBy::Depth(1), "./a/b/c.txt", "a"),
+            (GroupBy::Depth(2), "a/b/c/d.txt", "a/b"),
+            (GroupBy::Depth(2), "a/c.txt", "a"),
+            (GroupBy::Depth(1), "/usr/lib/x.so", "/usr"),
+            (GroupBy::Depth(1), "../x/y.txt", ".."),
+        ];
+        for (by, filepath, expected) in cases {
+            assert_eq!(by.key(filepath), expected, "{:?} {}", by, filepath);
+        }
+    }
+}
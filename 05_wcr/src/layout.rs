@@ -0,0 +1,46 @@
+use std::{
+    fs::{self, Metadata},
+    io,
+};
+
+/// Width of every count column, computed before reading the inputs the way GNU wc does.
+///
+/// The sizes of the regular files among `filepaths` are added up and the width is
+/// the number of digits in that total. Any other input, such as a pipe, could be
+/// arbitrarily large, so it raises the width to at least 7. Inputs that cannot be
+/// stat'ed are left out. With a single input and a single column nothing needs to
+/// line up, so the width is 1.
+pub(crate) fn number_width(filepaths: &[String], columns: usize) -> usize {
+    if filepaths.is_empty() || (filepaths.len() == 1 && columns == 1) {
+        return 1;
+    }
+    let mut minimum_width = 1;
+    let mut regular_total: u64 = 0;
+    for filepath in filepaths {
+        let metadata = match filepath.as_str() {
+            "-" => stdin_metadata(),
+            _ => fs::metadata(filepath),
+        };
+        match metadata {
+            Ok(metadata) if metadata.is_file() => regular_total += metadata.len(),
+            Ok(_) => minimum_width = 7,
+            Err(_) => {}
+        }
+    }
+    let digits = regular_total.checked_ilog10().unwrap_or(0) as usize + 1;
+    digits.max(minimum_width)
+}
+
+/// Metadata of whatever standard input is connected to
+#[cfg(unix)]
+pub(crate) fn stdin_metadata() -> io::Result<Metadata> {
+    use std::{fs::File, os::fd::AsFd};
+
+    let fd = io::stdin().as_fd().try_clone_to_owned()?;
+    File::from(fd).metadata()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn stdin_metadata() -> io::Result<Metadata> {
+    Err(io::ErrorKind::Unsupported.into())
+}
@@ -1,58 +1,90 @@
+mod counter;
+mod files0;
+mod group;
+mod layout;
+mod output;
+mod parallel;
+
 use std::{
     error::Error,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, ErrorKind},
+    ops::{Add, AddAssign},
     path::Path,
 };
 
-use clap::{command, Parser};
+use clap::Parser;
 
-#[derive(Debug, PartialEq)]
+pub use counter::{Counter, Encoding};
+pub use group::{GroupBy, SortKey};
+pub use output::{Format, Total};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct FileInfo {
     num_lines: usize,
     num_words: usize,
     num_bytes: usize,
     num_chars: usize,
+    num_max_line_length: usize,
 }
 
-pub fn count(mut handle: impl BufRead) -> Result<FileInfo, Box<dyn Error>> {
-    let mut num_lines = 0;
-    let mut num_words = 0;
-    let mut num_bytes = 0;
-    let mut num_chars = 0;
+/// Counts of two inputs taken together: sums of the counts, and the longer of the two longest lines
+impl Add for FileInfo {
+    type Output = FileInfo;
 
-    let mut line = String::new();
-    loop {
-        let read = handle.read_line(&mut line)?;
-        if read == 0 {
-            break;
+    fn add(self, other: FileInfo) -> FileInfo {
+        FileInfo {
+            num_lines: self.num_lines + other.num_lines,
+            num_words: self.num_words + other.num_words,
+            num_bytes: self.num_bytes + other.num_bytes,
+            num_chars: self.num_chars + other.num_chars,
+            num_max_line_length: self.num_max_line_length.max(other.num_max_line_length),
         }
-        num_lines += 1;
-        num_bytes += line.len();
-        num_chars += line.chars().count();
-        num_words += line.split_whitespace().count();
+    }
+}
 
-        line.clear();
+impl AddAssign for FileInfo {
+    fn add_assign(&mut self, other: FileInfo) {
+        *self = *self + other;
     }
+}
 
-    Ok(FileInfo {
-        num_lines,
-        num_words,
-        num_bytes,
-        num_chars,
-    })
+/// Feeds everything read from `handle` to `counter` in fixed-size chunks
+fn feed(mut handle: impl io::Read, counter: &mut Counter) -> io::Result<()> {
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let read = match handle.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        counter.update(&buffer[..read]);
+    }
+}
+
+pub fn count(
+    handle: impl io::Read,
+    encoding: Encoding,
+    max_line_length: bool,
+) -> Result<FileInfo, Box<dyn Error>> {
+    let mut counter = Counter::new(encoding, max_line_length);
+    feed(handle, &mut counter)?;
+    Ok(counter.finish())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{count, FileInfo};
+    use super::{count, Encoding, FileInfo};
     use std::io::Cursor;
 
     #[test]
     fn test_count() {
         // Act
         let text = "I don't want the world. I just want your half.\r\n";
-        let info = count(Cursor::new(text));
+        let info = count(Cursor::new(text), Encoding::Utf8, true);
 
         // Assert
         let expected = FileInfo {
@@ -60,6 +92,7 @@ mod tests {
             num_words: 10,
             num_bytes: 48,
             num_chars: 48,
+            num_max_line_length: 46,
         };
         assert_eq!(info.unwrap(), expected);
     }
@@ -83,6 +116,52 @@ pub struct Args {
         conflicts_with = "bytes"
     )]
     chars: bool,
+    #[arg(short = 'L', long, help = "Show the display width of the longest line")]
+    max_line_length: bool,
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        help = "Count each large regular file in N ranges on separate threads"
+    )]
+    threads: usize,
+    #[arg(
+        long = "files0-from",
+        value_name = "F",
+        conflicts_with = "files",
+        help = "Read NUL-terminated file names from F (- for standard input)"
+    )]
+    files0_from: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Format::Table,
+        help = "Output format; all but table name each field and include errors"
+    )]
+    format: Format,
+    #[arg(
+        long,
+        value_name = "WHEN",
+        value_enum,
+        default_value_t = Total::Auto,
+        help = "When to print a line with total counts"
+    )]
+    total: Total,
+    #[arg(
+        long,
+        value_name = "KEY",
+        help = "Print subtotals of files grouped by ext, dir or depth=N instead of each file"
+    )]
+    group_by: Option<GroupBy>,
+    #[arg(
+        long,
+        value_name = "COLUMN",
+        value_enum,
+        requires = "group_by",
+        help = "Order groups by a count, largest first, instead of by name"
+    )]
+    sort: Option<SortKey>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -98,6 +177,14 @@ pub struct Config {
     pub lines: bool,
     pub words: bool,
     pub mode: ByteCharMode,
+    pub max_line_length: bool,
+    pub threads: usize,
+    pub files0_from: Option<String>,
+    pub format: Format,
+    pub total: Total,
+    pub group_by: Option<GroupBy>,
+    pub sort: Option<SortKey>,
+    pub encoding: Encoding,
 }
 
 pub fn get_args() -> Result<Config, Box<dyn Error>> {
@@ -113,7 +200,10 @@ pub fn get_args() -> Result<Config, Box<dyn Error>> {
         ByteCharMode::None
     };
 
-    if [lines, words, args.bytes, args.chars].iter().all(|&x| !x) {
+    if [lines, words, args.bytes, args.chars, args.max_line_length]
+        .iter()
+        .all(|&x| !x)
+    {
         lines = true;
         words = true;
         mode = ByteCharMode::Bytes;
@@ -121,17 +211,23 @@ pub fn get_args() -> Result<Config, Box<dyn Error>> {
 
     Ok(Config {
         files: args.files,
-        lines: lines,
-        words: words,
+        lines,
+        words,
         mode,
+        max_line_length: args.max_line_length,
+        threads: args.threads,
+        files0_from: args.files0_from,
+        format: args.format,
+        total: args.total,
+        group_by: args.group_by,
+        sort: args.sort,
+        encoding: Encoding::from_env(),
     })
 }
 
 fn open(filepath: impl AsRef<Path>) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
     match filepath.as_ref().to_str() {
-        None => {
-            return Err("Invalid Unicode in file path".into());
-        }
+        None => Err("Invalid Unicode in file path".into()),
         Some("-") => {
             let stdin = io::stdin();
             Ok(Box::new(BufReader::new(stdin.lock())))
@@ -143,60 +239,146 @@ fn open(filepath: impl AsRef<Path>) -> Result<Box<dyn BufRead>, Box<dyn Error>>
     }
 }
 
-fn format_value(value: usize, show: bool) -> String {
-    if show {
-        format!("{:>8}", value)
-    } else {
-        "".to_string()
+impl Config {
+    /// The counts to show with their field names, in the order GNU wc prints them
+    fn columns(&self, info: &FileInfo) -> Vec<(&'static str, usize)> {
+        [
+            ("lines", info.num_lines, self.lines),
+            ("words", info.num_words, self.words),
+            ("chars", info.num_chars, self.mode == ByteCharMode::Chars),
+            ("bytes", info.num_bytes, self.mode == ByteCharMode::Bytes),
+            (
+                "max_line_length",
+                info.num_max_line_length,
+                self.max_line_length,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, value, show)| show.then_some((name, value)))
+        .collect()
+    }
+
+    fn counts(&self, info: &FileInfo) -> Vec<usize> {
+        self.columns(info)
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect()
     }
 }
 
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let mut total_lines = 0;
-    let mut total_words = 0;
-    let mut total_bytes = 0;
-    let mut total_chars = 0;
+    let mut total = FileInfo::default();
 
-    let filepaths = if config.files.len() == 0 {
-        &vec!["-".to_string()]
+    let list = match &config.files0_from {
+        Some(source) => Some(files0::read(source)?),
+        None => None,
+    };
+    let entries = match &list {
+        Some(list) => list.entries.clone(),
+        None if config.files.is_empty() => vec![Ok("-".to_string())],
+        None => config.files.iter().cloned().map(Ok).collect(),
+    };
+    let columns: Vec<&str> = config
+        .columns(&FileInfo::default())
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    let stat_files = list.as_ref().is_none_or(|list| list.stat_files);
+    // A lone total row has nothing to line up with
+    let width = if stat_files && config.total != Total::Only {
+        let filepaths: Vec<String> = entries.iter().flatten().cloned().collect();
+        layout::number_width(&filepaths, columns.len())
     } else {
-        &config.files
+        1
+    };
+    let output = output::Output::new(config.format, width, columns, config.group_by.is_some());
+    // Entries of a `--files0-from` list are reported with their position in the list
+    let prefix = |index: usize| {
+        config
+            .files0_from
+            .as_ref()
+            .map(|source| format!("{}:{}", source, index + 1))
     };
 
-    for filepath in filepaths {
-        match open(filepath) {
-            Err(err) => eprintln!("{}: {}", filepath, err),
-            Ok(handle) => {
-                let info = count(handle)?;
-                total_lines += info.num_lines;
-                total_words += info.num_words;
-                total_bytes += info.num_bytes;
-                total_chars += info.num_chars;
-
-                println!(
-                    "{}{}{}{}{}",
-                    format_value(info.num_lines, config.lines),
-                    format_value(info.num_words, config.words),
-                    format_value(info.num_bytes, config.mode == ByteCharMode::Bytes),
-                    format_value(info.num_chars, config.mode == ByteCharMode::Chars),
-                    match filepath.as_str() {
-                        "-" => "".to_string(),
-                        _ => format!(" {}", filepath),
-                    }
-                );
+    let mut groups = config.group_by.map(group::Groups::new);
+    // Files that could not be counted, or only in part; the others are still counted
+    let mut failed = 0;
+    for (index, entry) in entries.iter().enumerate() {
+        let prefix = prefix(index);
+        let filepath = match entry {
+            Ok(filepath) => filepath,
+            Err(err) => {
+                output.error(prefix.as_deref(), None, err);
+                failed += 1;
+                continue;
             }
+        };
+        // A read error keeps the counts up to the failure, as GNU wc does. A failed
+        // parallel count has no such counts, so the file is only reported.
+        let counted = parallel::count_file(filepath, &config).map(|counted| match counted {
+            parallel::Counted::Parallel(info) => (info, None),
+            parallel::Counted::Stream(handle) => {
+                let mut counter = Counter::new(config.encoding, config.max_line_length);
+                let result = feed(handle, &mut counter);
+                (counter.finish(), result.err())
+            }
+        });
+        let (info, error) = match counted {
+            Ok(counted) => counted,
+            Err(err) => {
+                output.error(prefix.as_deref(), Some(filepath), &err.to_string());
+                failed += 1;
+                continue;
+            }
+        };
+        total += info;
+        if let Some(groups) = &mut groups {
+            groups.add(filepath, info);
+        }
+
+        // Standard input read because no files were given has no name
+        let name = (list.is_some() || !config.files.is_empty()).then_some(filepath.as_str());
+        // Files have no rows of their own when grouped or when only the total is shown
+        let file_rows = groups.is_none() && config.total != Total::Only;
+        match error {
+            Some(err) => {
+                failed += 1;
+                if !file_rows {
+                    output.error(prefix.as_deref(), name, &err.to_string());
+                } else {
+                    output.partial(
+                        prefix.as_deref(),
+                        name,
+                        &config.counts(&info),
+                        &err.to_string(),
+                    );
+                }
+            }
+            None if file_rows => output.counts(name, &config.counts(&info)),
+            None => {}
         }
     }
 
-    if config.files.len() > 1 {
-        println!(
-            "{}{}{}{} total",
-            format_value(total_lines, config.lines),
-            format_value(total_words, config.words),
-            format_value(total_bytes, config.mode == ByteCharMode::Bytes),
-            format_value(total_chars, config.mode == ByteCharMode::Chars),
-        );
+    if let Some(groups) = groups {
+        if config.total != Total::Only {
+            for (name, info) in groups.sorted(config.sort) {
+                output.group(&name, &config.counts(&info));
+            }
+        }
+    }
+
+    // Subtotals of groups come with a grand total unless it is turned off
+    let show_total = match config.total {
+        Total::Auto => entries.len() > 1 || config.group_by.is_some(),
+        Total::Always | Total::Only => true,
+        Total::Never => false,
+    };
+    if show_total {
+        output.total(&config.counts(&total), config.total != Total::Only);
     }
 
+    if failed > 0 {
+        return Err(format!("{} of {} files could not be counted", failed, entries.len()).into());
+    }
     Ok(())
 }
@@ -0,0 +1,228 @@
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Right-aligned columns like GNU wc
+    Table,
+    /// One JSON object per line
+    Json,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Tab-separated values with a header row
+    Tsv,
+}
+
+/// When to print the total row, as GNU wc's `--total`
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Total {
+    /// When more than one file is counted
+    Auto,
+    Always,
+    /// Print the total only, without a label in a table
+    Only,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    File,
+    Error,
+    Group,
+    Total,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::File => "file",
+            Kind::Error => "error",
+            Kind::Group => "group",
+            Kind::Total => "total",
+        }
+    }
+}
+
+/// Writes one row per file, error, group and total in the chosen format.
+///
+/// Machine-readable formats name every field and mark each row with a `type` of
+/// `file`, `error`, `group` or `total`, so that a file named "total" or containing spaces
+/// cannot be mistaken for anything else.
+pub(crate) struct Output {
+    format: Format,
+    /// Column width for [`Format::Table`]
+    width: usize,
+    /// Names of the counts shown, in the order they are passed to each row
+    columns: Vec<&'static str>,
+    /// Whether rows are subtotals of `--group-by` groups, which CSV and TSV name in a column of their own
+    grouped: bool,
+}
+
+impl Output {
+    pub(crate) fn new(
+        format: Format,
+        width: usize,
+        columns: Vec<&'static str>,
+        grouped: bool,
+    ) -> Self {
+        let output = Output {
+            format,
+            width,
+            columns,
+            grouped,
+        };
+        if matches!(format, Format::Csv | Format::Tsv) {
+            let header: Vec<&str> = ["type", "file"]
+                .into_iter()
+                .chain(grouped.then_some("group"))
+                .chain(output.columns.iter().copied())
+                .chain(["error"])
+                .collect();
+            output.write_fields(&header);
+        }
+        output
+    }
+
+    pub(crate) fn counts(&self, file: Option<&str>, counts: &[usize]) {
+        self.row(Kind::File, file, Some(counts), None);
+    }
+
+    /// Writes the subtotal of the files in the group named `name`
+    pub(crate) fn group(&self, name: &str, counts: &[usize]) {
+        self.row(Kind::Group, Some(name), Some(counts), None);
+    }
+
+    /// Writes the total row, labeled "total" in a table only when `labeled`
+    pub(crate) fn total(&self, counts: &[usize], labeled: bool) {
+        let label = labeled.then_some("total");
+        self.row(Kind::Total, label, Some(counts), None);
+    }
+
+    /// Reports a file that could not be counted. `prefix` locates it for a
+    /// table, for example its position in a `--files0-from` list.
+    pub(crate) fn error(&self, prefix: Option<&str>, file: Option<&str>, error: &str) {
+        if self.format == Format::Table {
+            let location: Vec<&str> = prefix.into_iter().chain(file).collect();
+            if location.is_empty() {
+                eprintln!("{}", error);
+            } else {
+                eprintln!("{}: {}", location.join(": "), error);
+            }
+            return;
+        }
+        self.row(Kind::Error, file, None, Some(error));
+    }
+
+    /// Reports a file whose reading failed part way, with the counts up to the failure.
+    /// A table shows the error and the counts apart; other formats put both in one row.
+    pub(crate) fn partial(
+        &self,
+        prefix: Option<&str>,
+        file: Option<&str>,
+        counts: &[usize],
+        error: &str,
+    ) {
+        if self.format == Format::Table {
+            self.error(prefix, file, error);
+            self.counts(file, counts);
+            return;
+        }
+        self.row(Kind::File, file, Some(counts), Some(error));
+    }
+
+    /// `file` is the name of the file, the group or the label of the total
+    fn row(&self, kind: Kind, file: Option<&str>, counts: Option<&[usize]>, error: Option<&str>) {
+        match self.format {
+            Format::Table => {
+                let mut row = counts
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|count| format!("{:>width$}", count, width = self.width))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if let Some(name) = file {
+                    row.push(' ');
+                    row.push_str(name);
+                }
+                println!("{}", row);
+            }
+            Format::Json => {
+                let mut object = Map::new();
+                object.insert("type".to_string(), kind.name().into());
+                match (kind, file) {
+                    (Kind::File | Kind::Error, Some(file)) => {
+                        object.insert("file".to_string(), file.into());
+                    }
+                    (Kind::Group, Some(group)) => {
+                        object.insert("group".to_string(), group.into());
+                    }
+                    _ => {}
+                }
+                for (&column, &count) in self.columns.iter().zip(counts.unwrap_or_default()) {
+                    object.insert(column.to_string(), count.into());
+                }
+                if let Some(error) = error {
+                    object.insert("error".to_string(), error.into());
+                }
+                println!("{}", Value::Object(object));
+            }
+            Format::Csv | Format::Tsv => {
+                let counts: Vec<String> = match counts {
+                    Some(counts) => counts.iter().map(usize::to_string).collect(),
+                    None => vec![String::new(); self.columns.len()],
+                };
+                let (file, group) = match kind {
+                    Kind::File | Kind::Error => (file.unwrap_or(""), ""),
+                    Kind::Group => ("", file.unwrap_or("")),
+                    Kind::Total => ("", ""),
+                };
+                let fields: Vec<&str> = [kind.name(), file]
+                    .into_iter()
+                    .chain(self.grouped.then_some(group))
+                    .chain(counts.iter().map(String::as_str))
+                    .chain([error.unwrap_or("")])
+                    .collect();
+                self.write_fields(&fields);
+            }
+        }
+    }
+
+    fn write_fields(&self, fields: &[&str]) {
+        let (separator, escape): (&str, fn(&str) -> String) = match self.format {
+            Format::Tsv => ("\t", escape_tsv),
+            _ => (",", escape_csv),
+        };
+        let fields: Vec<String> = fields.iter().map(|field| escape(field)).collect();
+        println!("{}", fields.join(separator));
+    }
+}
+
+/// Quotes a field as RFC 4180 requires when it contains a separator, quote or line break
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// TSV has no quoting, so tabs, line breaks and backslashes are written as escapes
+fn escape_tsv(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_fields() {
+        assert_eq!(escape_csv("plain name.txt"), "plain name.txt");
+        assert_eq!(escape_csv("a,\"b\".txt"), "\"a,\"\"b\"\".txt\"");
+        assert_eq!(escape_tsv("a\tb\\c\n"), "a\\tb\\\\c\\n");
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    thread,
+};
+
+use crate::{
+    counter::{Counter, Part},
+    feed, Config, Encoding, FileInfo,
+};
+
+/// Ranges smaller than this are not worth a thread of their own
+const MIN_RANGE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// An input either counted in parallel or opened to be counted as a single stream
+pub(crate) enum Counted {
+    Parallel(FileInfo),
+    Stream(Box<dyn BufRead>),
+}
+
+/// Counts a regular file on up to `config.threads` threads, each reading its own range.
+///
+/// Anything else is opened once and handed back as a stream: standard input, anything
+/// that is not a regular file (a FIFO cannot be opened a second time), or a file too
+/// small to split.
+pub(crate) fn count_file(filepath: &str, config: &Config) -> Result<Counted, Box<dyn Error>> {
+    if config.threads <= 1 || filepath == "-" {
+        return crate::open(filepath).map(Counted::Stream);
+    }
+    let mut file = File::open(filepath)?;
+    let metadata = file.metadata()?;
+    let parts = (metadata.len() / MIN_RANGE_SIZE).min(config.threads as u64) as usize;
+    if !metadata.is_file() || parts <= 1 {
+        return Ok(Counted::Stream(Box::new(BufReader::new(file))));
+    }
+    let boundaries = split(&mut file, metadata.len(), parts, config.max_line_length)?;
+    let info = count_ranges(
+        filepath,
+        &boundaries,
+        config.encoding,
+        config.max_line_length,
+    )?;
+    Ok(Counted::Parallel(info))
+}
+
+/// Offsets dividing a file of `len` bytes into `parts` ranges of about the same size.
+///
+/// Each offset is moved forward past continuation bytes so that no UTF-8 sequence
+/// is cut. With `at_lines` it is moved to the start of the next line instead, so
+/// that line widths, which depend on the column a tab starts at, stay exact.
+fn split(file: &mut File, len: u64, parts: usize, at_lines: bool) -> io::Result<Vec<u64>> {
+    let mut boundaries = vec![0];
+    for i in 1..parts {
+        let previous = *boundaries.last().expect("starts with 0");
+        let offset = previous.max(len * i as u64 / parts as u64);
+        let boundary = if at_lines {
+            next_line_start(file, offset, len)?
+        } else {
+            next_char_start(file, offset)?
+        };
+        boundaries.push(boundary);
+    }
+    boundaries.push(len);
+    Ok(boundaries)
+}
+
+fn next_char_start(file: &mut File, offset: u64) -> io::Result<u64> {
+    // A UTF-8 sequence has at most 3 continuation bytes
+    let mut bytes = Vec::with_capacity(3);
+    file.seek(SeekFrom::Start(offset))?;
+    file.by_ref().take(3).read_to_end(&mut bytes)?;
+    let continuation = bytes
+        .iter()
+        .take_while(|&&byte| (0x80..=0xbf).contains(&byte))
+        .count();
+    Ok(offset + continuation as u64)
+}
+
+fn next_line_start(file: &mut File, offset: u64, len: u64) -> io::Result<u64> {
+    if offset == 0 {
+        return Ok(0);
+    }
+    // Searching from the byte before `offset` keeps an offset that already starts a line
+    let mut position = offset - 1;
+    file.seek(SeekFrom::Start(position))?;
+    let mut buffer = vec![0; crate::CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(len);
+        }
+        if let Some(newline) = memchr::memchr(b'\n', &buffer[..read]) {
+            return Ok(position + newline as u64 + 1);
+        }
+        position += read as u64;
+    }
+}
+
+/// Counts each range between consecutive `boundaries` on its own thread and joins the counts in order
+fn count_ranges(
+    filepath: &str,
+    boundaries: &[u64],
+    encoding: Encoding,
+    max_line_length: bool,
+) -> io::Result<FileInfo> {
+    let parts = thread::scope(|scope| {
+        let handles: Vec<_> = boundaries
+            .windows(2)
+            .map(|range| {
+                let (start, end) = (range[0], range[1]);
+                scope.spawn(move || -> io::Result<Part> {
+                    let mut file = File::open(filepath)?;
+                    file.seek(SeekFrom::Start(start))?;
+                    let mut counter = Counter::new(encoding, max_line_length);
+                    feed(file.take(end - start), &mut counter)?;
+                    Ok(counter.finish_part())
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("counting thread panicked"))
+            .collect::<io::Result<Vec<Part>>>()
+    })?;
+    Ok(parts
+        .into_iter()
+        .reduce(Part::join)
+        .map(|part| part.info)
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, io::Cursor, process};
+
+    use super::*;
+    use crate::count;
+
+    #[test]
+    fn test_split_counts_match_sequential_count() {
+        let text = [
+            "日本語の word\tsplit across\n".repeat(7).as_bytes(),
+            b"\x01ctrl \xff\xe3\x81 broken  ",
+            "long line without newline ".repeat(9).as_bytes(),
+            b"\n\n  tail",
+        ]
+        .concat();
+        let path = env::temp_dir().join(format!("wcr-parallel-{}", process::id()));
+        fs::write(&path, &text).unwrap();
+        let filepath = path.to_str().unwrap();
+
+        for encoding in [Encoding::Utf8, Encoding::SingleByte] {
+            for max_line_length in [false, true] {
+                let expected = count(Cursor::new(&text), encoding, max_line_length).unwrap();
+                let mut file = File::open(filepath).unwrap();
+                let len = text.len() as u64;
+                // Every possible boundary between two ranges, then splits into more ranges
+                let mut splits: Vec<Vec<u64>> = (1..len)
+                    .map(|offset| {
+                        let boundary = if max_line_length {
+                            next_line_start(&mut file, offset, len)
+                        } else {
+                            next_char_start(&mut file, offset)
+                        };
+                        vec![0, boundary.unwrap(), len]
+                    })
+                    .collect();
+                splits.extend(
+                    (3..=16).map(|parts| split(&mut file, len, parts, max_line_length).unwrap()),
+                );
+                for boundaries in splits {
+                    let actual =
+                        count_ranges(filepath, &boundaries, encoding, max_line_length).unwrap();
+                    assert_eq!(actual, expected, "{:?} at {:?}", encoding, boundaries);
+                }
+            }
+        }
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -41,7 +41,12 @@ fn dies_chars_and_bytes() -> Result<()> {
 // --------------------------------------------------
 fn run(args: &[&str], expected_file: &str) -> Result<()> {
     let expected = fs::read_to_string(expected_file)?;
-    let output = Command::cargo_bin(PRG)?.args(args).output().expect("fail");
+    // The expected files are made by GNU wc in a UTF-8 locale (see mk-outs.sh)
+    let output = Command::cargo_bin(PRG)?
+        .args(args)
+        .env("LC_ALL", "C.UTF-8")
+        .output()
+        .expect("fail");
     assert!(output.status.success());
 
     let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
@@ -58,8 +63,11 @@ fn skips_bad_file() -> Result<()> {
     Command::cargo_bin(PRG)?
         .arg(bad)
         .assert()
-        .success()
-        .stderr(predicate::str::is_match(expected)?);
+        .code(1)
+        .stderr(predicate::str::is_match(expected)?)
+        .stderr(predicate::str::ends_with(
+            "1 of 1 files could not be counted\n",
+        ));
     Ok(())
 }
 
@@ -166,6 +174,7 @@ fn atlamal_stdin() -> Result<()> {
     let expected = fs::read_to_string("tests/expected/atlamal.txt.stdin.out")?;
 
     let output = Command::cargo_bin(PRG)?
+        .env("LC_ALL", "C.UTF-8")
         .write_stdin(input)
         .output()
         .expect("fail");
@@ -217,3 +226,380 @@ fn test_all_words_lines() -> Result<()> {
 fn test_all_bytes_lines() -> Result<()> {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf8_stdin() -> Result<()> {
+    let input: &[u8] = b"a\xffb \xff\nx\xe3\x81 y\n";
+    Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("      2       3      12\n");
+    Command::cargo_bin(PRG)?
+        .args(["-m"])
+        .env("LC_ALL", "C.UTF-8")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("8\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn max_line_length_all() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-L", ATLAMAL, EMPTY, FOX])
+        .env("LC_ALL", "C.UTF-8")
+        .assert()
+        .success()
+        .stdout(concat!(
+            " 43 tests/inputs/atlamal.txt\n",
+            "  0 tests/inputs/empty.txt\n",
+            " 50 tests/inputs/fox.txt\n",
+            " 50 total\n",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn max_line_length_wide_chars() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-lL"])
+        .env("LC_ALL", "C.UTF-8")
+        .write_stdin("日本語\ta\nabc\n")
+        .assert()
+        .success()
+        .stdout("      2       9\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn unset_locale_is_c() -> Result<()> {
+    // One character per byte, and the bytes of "þá" are neither printable nor
+    // spaces, so it is not a word as it is in a UTF-8 locale
+    Command::cargo_bin(PRG)?
+        .args(["-wm", ATLAMAL])
+        .env_remove("LC_ALL")
+        .env_remove("LC_CTYPE")
+        .env_remove("LANG")
+        .assert()
+        .success()
+        .stdout(" 28 177 tests/inputs/atlamal.txt\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn threads_match_single_thread() -> Result<()> {
+    // Large enough to be split into several ranges
+    let path = std::env::temp_dir().join(format!("wcr-threads-{}", std::process::id()));
+    let line = "日本語 word\tand more words across   ranges\n";
+    fs::write(&path, line.repeat(12 * 1024 * 1024 / line.len()))?;
+    let path = path.to_str().expect("temp path is UTF-8");
+
+    for flags in ["-lwc", "-mL"] {
+        let single = Command::cargo_bin(PRG)?
+            .args([flags, path])
+            .env("LC_ALL", "C.UTF-8")
+            .output()?;
+        let threaded = Command::cargo_bin(PRG)?
+            .args([flags, "--threads", "4", path])
+            .env("LC_ALL", "C.UTF-8")
+            .output()?;
+        assert!(threaded.status.success());
+        assert_eq!(
+            String::from_utf8(threaded.stdout)?,
+            String::from_utf8(single.stdout)?
+        );
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn threads_read_fifo_once() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("wcr-fifo-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let fifo = dir.join("p");
+    assert!(std::process::Command::new("mkfifo")
+        .arg(&fifo)
+        .status()?
+        .success());
+    // Opening a FIFO for writing waits for the reader, so write from another thread
+    let writer = {
+        let fifo = fifo.clone();
+        std::thread::spawn(move || fs::write(fifo, "a b\nc\n"))
+    };
+    let fifo = fifo.to_str().expect("temp path is UTF-8");
+
+    Command::cargo_bin(PRG)?
+        .args(["--threads", "2", fifo])
+        .timeout(std::time::Duration::from_secs(10))
+        .assert()
+        .success()
+        .stdout(format!("      2       3       6 {fifo}\n"));
+    writer.join().expect("writer panicked")?;
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn threads_report_bad_file_once() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--threads", "4", &bad])
+        .assert()
+        .code(1)
+        .stderr(format!(
+            "{bad}: No such file or directory (os error 2)\n1 of 1 files could not be counted\n"
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_zero_threads() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--threads", "0", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value '0'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_bad_file_in_width() -> Result<()> {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-l", FOX, &bad, ATLAMAL])
+        .assert()
+        .failure()
+        .stdout("  1 tests/inputs/fox.txt\n  4 tests/inputs/atlamal.txt\n  5 total\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn named_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "-"])
+        .write_stdin("a\n")
+        .assert()
+        .success()
+        .stdout("1 -\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_file() -> Result<()> {
+    let list = std::env::temp_dir().join(format!("wcr-files0-{}", std::process::id()));
+    fs::write(&list, format!("{FOX}\0\0nonexist\0{ATLAMAL}"))?;
+    let list = list.to_str().expect("temp path is UTF-8");
+
+    Command::cargo_bin(PRG)?
+        .arg(format!("--files0-from={list}"))
+        .env("LC_ALL", "C.UTF-8")
+        .assert()
+        .failure()
+        .stdout(concat!(
+            "  1   9  48 tests/inputs/fox.txt\n",
+            "  4  29 177 tests/inputs/atlamal.txt\n",
+            "  5  38 225 total\n",
+        ))
+        .stderr(
+            predicate::str::contains(format!("{list}:2: invalid zero-length file name"))
+                .and(predicate::str::contains(format!("{list}:3: nonexist: "))),
+        );
+    fs::remove_file(list)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_stdin() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--files0-from", "-"])
+        .write_stdin(format!("{FOX}\0-\0{ATLAMAL}\0"))
+        .assert()
+        .failure()
+        .stdout("1 tests/inputs/fox.txt\n4 tests/inputs/atlamal.txt\n5 total\n")
+        .stderr(predicate::str::contains(
+            "-:2: when reading file names from standard input",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_files0_from_and_files() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", "-", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn format_json_with_error() -> Result<()> {
+    let bad = gen_bad_file();
+    let output = Command::cargo_bin(PRG)?
+        .args(["--format", "json", "-lw", FOX, &bad])
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    let rows: Vec<&str> = stdout.lines().collect();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(
+        rows[0],
+        r#"{"type":"file","file":"tests/inputs/fox.txt","lines":1,"words":9}"#
+    );
+    assert!(rows[1].starts_with(&format!(r#"{{"type":"error","file":"{bad}","error":"#)));
+    assert_eq!(rows[2], r#"{"type":"total","lines":1,"words":9}"#);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stderr, b"1 of 2 files could not be counted\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn format_csv_and_tsv() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--format", "csv", "-m", FOX, ATLAMAL])
+        .env("LC_ALL", "C.UTF-8")
+        .assert()
+        .success()
+        .stdout(concat!(
+            "type,file,chars,error\n",
+            "file,tests/inputs/fox.txt,48,\n",
+            "file,tests/inputs/atlamal.txt,159,\n",
+            "total,,207,\n",
+        ));
+    Command::cargo_bin(PRG)?
+        .args(["--format", "tsv", "-l", FOX])
+        .assert()
+        .success()
+        .stdout("type\tfile\tlines\terror\nfile\ttests/inputs/fox.txt\t1\t\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn total_modes() -> Result<()> {
+    let cases = [
+        ("auto", vec![FOX], "1 tests/inputs/fox.txt\n"),
+        (
+            "auto",
+            vec![FOX, ATLAMAL],
+            "  1 tests/inputs/fox.txt\n  4 tests/inputs/atlamal.txt\n  5 total\n",
+        ),
+        ("always", vec![FOX], "1 tests/inputs/fox.txt\n1 total\n"),
+        ("only", vec![FOX, ATLAMAL], "5\n"),
+        (
+            "never",
+            vec![FOX, ATLAMAL],
+            "  1 tests/inputs/fox.txt\n  4 tests/inputs/atlamal.txt\n",
+        ),
+    ];
+    for (mode, files, expected) in cases {
+        Command::cargo_bin(PRG)?
+            .arg("-l")
+            .arg(format!("--total={mode}"))
+            .args(files)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn total_only_has_no_padding() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--total=only", FOX, ATLAMAL])
+        .env("LC_ALL", "C.UTF-8")
+        .assert()
+        .success()
+        .stdout("5 38 225\n");
+    Command::cargo_bin(PRG)?
+        .args(["--total=only", "--format=json", "-c", FOX])
+        .assert()
+        .success()
+        .stdout("{\"type\":\"total\",\"bytes\":48}\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn read_error_keeps_partial_counts() -> Result<()> {
+    // A directory opens but fails on the first read, leaving empty counts
+    Command::cargo_bin(PRG)?
+        .args(["-l", FOX, "tests", ATLAMAL])
+        .assert()
+        .code(1)
+        .stdout(concat!(
+            "      1 tests/inputs/fox.txt\n",
+            "      0 tests\n",
+            "      4 tests/inputs/atlamal.txt\n",
+            "      5 total\n",
+        ))
+        .stderr("tests: Is a directory (os error 21)\n1 of 3 files could not be counted\n");
+    Command::cargo_bin(PRG)?
+        .args(["--format=csv", "-l", "tests"])
+        .assert()
+        .code(1)
+        .stdout("type,file,lines,error\nfile,tests,0,Is a directory (os error 21)\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn group_by_sorted() -> Result<()> {
+    let files = [FOX, "tests/expected/all.out", ATLAMAL];
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--group-by=ext"])
+        .args(files)
+        .assert()
+        .success()
+        .stdout("  4 .out\n  5 .txt\n  9 total\n");
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--group-by=ext", "--sort=lines"])
+        .args(files)
+        .assert()
+        .success()
+        .stdout("  5 .txt\n  4 .out\n  9 total\n");
+    Command::cargo_bin(PRG)?
+        .args(["-w", "--group-by=depth=1", "--format=tsv", "--total=never"])
+        .args(files)
+        .env("LC_ALL", "C.UTF-8")
+        .assert()
+        .success()
+        .stdout("type\tfile\tgroup\twords\terror\ngroup\t\ttests\t54\t\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_group_by_or_sort_alone() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--group-by=depth=0", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected ext, dir or depth=N"));
+    Command::cargo_bin(PRG)?
+        .args(["--sort=lines", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--group-by <KEY>"));
+    Ok(())
+}
@@ -4,6 +4,9 @@ ROOT="tests/inputs"
 FILES="$ROOT/empty.txt $ROOT/fox.txt $ROOT/atlamal.txt"
 OUT_DIR="tests/expected"
 
+# Expected outputs come from GNU coreutils wc, counting characters as UTF-8
+export LC_ALL=C.UTF-8
+
 [[ ! -d "$OUT_DIR" ]] && mkdir -p "$OUT_DIR"
 
 for FILE in $FILES; do
@@ -21,7 +24,8 @@ for FILE in $FILES; do
     wc -ml  $FILE > ${OUT_DIR}/${BASENAME}.ml.out
 done
 
-wc < "$ROOT/atlamal.txt" > "$OUT_DIR/atlamal.txt.stdin.out"
+# Piped like the tests feed stdin; wc sizes its columns differently for a redirected file
+cat "$ROOT/atlamal.txt" | wc > "$OUT_DIR/atlamal.txt.stdin.out"
 
 wc      $FILES > $OUT_DIR/all.out
 wc -l   $FILES > $OUT_DIR/all.l.out